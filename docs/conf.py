@@ -52,5 +52,5 @@ autodoc_member_order = "bysource"
 autodoc_typehints = "description"
 
 doctest_global_setup = """
-from crabwalk import Override, Overrides, Types, Walk
+from crabwalk import CompiledOverrides, Gitignore, Match, Override, Overrides, Types, Walk
 """
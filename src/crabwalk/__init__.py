@@ -1,29 +1,51 @@
-from ._lib import DirEntry, Types, Walk
+from ._lib import (
+    CompiledOverrides,
+    DirEntry,
+    Gitignore,
+    Metadata,
+    Types,
+    Walk,
+    disk_usage,
+)
+from ._tree import walk_tree
 from ._types import (
+    DiskUsage,
     GlobError,
     InvalidDefinitionError,
     LoopError,
+    Match,
     Override,
     Overrides,
     PartialError,
     UnrecognizedFileTypeError,
     WalkError,
+    WalkStats,
 )
 
+DiskUsage.__module__ = __name__
 GlobError.__module__ = __name__
 InvalidDefinitionError.__module__ = __name__
 LoopError.__module__ = __name__
+Match.__module__ = __name__
 Override.__module__ = __name__
 Overrides.__module__ = __name__
 PartialError.__module__ = __name__
 UnrecognizedFileTypeError.__module__ = __name__
 WalkError.__module__ = __name__
+WalkStats.__module__ = __name__
+disk_usage.__module__ = __name__
+walk_tree.__module__ = __name__
 
 __all__ = (
+    "CompiledOverrides",
     "DirEntry",
+    "DiskUsage",
+    "Gitignore",
     "GlobError",
     "InvalidDefinitionError",
     "LoopError",
+    "Match",
+    "Metadata",
     "Override",
     "Overrides",
     "PartialError",
@@ -31,4 +53,7 @@ __all__ = (
     "UnrecognizedFileTypeError",
     "Walk",
     "WalkError",
+    "WalkStats",
+    "disk_usage",
+    "walk_tree",
 )
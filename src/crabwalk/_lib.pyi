@@ -1,17 +1,21 @@
+import asyncio
 import os
 from collections.abc import Callable, Iterable, Iterator, MutableMapping, Sequence
+from datetime import datetime
 from types import TracebackType
-from typing import Any, Protocol, TypeVar, overload
+from typing import Any, AnyStr, Generic, Literal, Protocol, TypeVar, overload
 
 from typing_extensions import TypeAlias, final
 
-from ._types import Overrides
+from ._types import DiskUsage, Match, Overrides, WalkStats
 
 StrPath: TypeAlias = str | os.PathLike[str]
+BytesPath: TypeAlias = bytes | os.PathLike[bytes]
 
 KT = TypeVar("KT")
 VT_co = TypeVar("VT_co", covariant=True)
 T = TypeVar("T")
+T_co = TypeVar("T_co", covariant=True)
 T_contra = TypeVar("T_contra", contravariant=True)
 
 class SupportsDunderLT(Protocol[T_contra]):
@@ -22,22 +26,74 @@ class SupportsDunderGT(Protocol[T_contra]):
 
 SupportsRichComparison: TypeAlias = SupportsDunderLT[Any] | SupportsDunderGT[Any]
 
+SortMode: TypeAlias = Literal[
+    "name", "name_ci", "natural", "mtime", "size", "dirs_first"
+]
+
 class SupportsKeysAndGetItem(Protocol[KT, VT_co]):
     def keys(self) -> Iterable[KT]: ...
     def __getitem__(self, __key: KT) -> VT_co: ...
 
 @final
-class DirEntry:
-    name: str
-    path: str
+class DirEntry(Generic[AnyStr]):
+    name: AnyStr
+    path: AnyStr
     def inode(self) -> int: ...
-    def is_dir(self) -> bool: ...
-    def is_file(self) -> bool: ...
+    def is_dir(self, *, follow_symlinks: bool | None = ...) -> bool: ...
+    def is_file(self, *, follow_symlinks: bool | None = ...) -> bool: ...
     def is_symlink(self) -> bool: ...
-    def stat(self) -> os.stat_result: ...
-    def __fspath__(self) -> str: ...
+    def is_junction(self) -> bool: ...
+    def stat(self, *, follow_symlinks: bool | None = ...) -> os.stat_result: ...
+    def metadata(self) -> Metadata: ...
+    def __fspath__(self) -> AnyStr: ...
     depth: int
     follow_symlinks: bool
+    root: AnyStr
+    relative_path: AnyStr
+
+@final
+class Metadata:
+    @property
+    def size(self) -> int: ...
+    @property
+    def mtime_ns(self) -> int: ...
+    @property
+    def mode(self) -> int: ...
+    @property
+    def uid(self) -> int: ...
+    @property
+    def gid(self) -> int: ...
+    @property
+    def nlink(self) -> int: ...
+    @property
+    def dev(self) -> int: ...
+
+@final
+class Gitignore:
+    def __init__(self, path: StrPath, *, case_insensitive: bool = ...) -> None: ...
+    def add(self, path: StrPath) -> None: ...
+    def add_line(self, line: str, *, source: StrPath | None = ...) -> None: ...
+    @property
+    def path(self) -> str: ...
+    @property
+    def num_ignores(self) -> int: ...
+    @property
+    def num_whitelists(self) -> int: ...
+    def matched(self, path: StrPath, is_dir: bool = ...) -> Match | None: ...
+    def matched_path_or_any_parents(
+        self, path: StrPath, is_dir: bool = ...
+    ) -> Match | None: ...
+
+@final
+class CompiledOverrides:
+    def __init__(self, overrides: Overrides) -> None: ...
+    @property
+    def path(self) -> str: ...
+    @property
+    def num_ignores(self) -> int: ...
+    @property
+    def num_whitelists(self) -> int: ...
+    def matched(self, path: StrPath, is_dir: bool = ...) -> Match | None: ...
 
 VIn: TypeAlias = Sequence[str]
 VOut: TypeAlias = tuple[str, ...]
@@ -65,18 +121,70 @@ class Types(MutableMapping[str, VOut]):
     @overload
     def update(self, **kwargs: VIn) -> None: ...
     def add(self, name: str, glob: str) -> None: ...
+    def add_def(self, definition: str) -> None: ...
     def add_defaults(self) -> None: ...
+    @classmethod
+    def from_file(cls, path: StrPath) -> Types: ...
     def select(self, name: str) -> None: ...
     def negate(self, name: str) -> None: ...
+    def matched(self, path: StrPath, is_dir: bool = ...) -> Match | None: ...
+    def type_of(self, path: StrPath) -> Match | None: ...
 
 @final
-class Walk:
+class Walk(Generic[T_co]):
+    @overload
     def __new__(
         cls,
         *paths: StrPath,
         max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[str], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[str]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[str]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[str, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: None = ...,
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[DirEntry[str]]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: BytesPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
         follow_symlinks: bool = ...,
         max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
         global_ignore_files: Sequence[StrPath] | None = ...,
         custom_ignore_filenames: Sequence[str] | None = ...,
         overrides: Overrides | None = ...,
@@ -89,19 +197,296 @@ class Walk:
         git_exclude: bool = ...,
         require_git: bool = ...,
         ignore_case_insensitive: bool = ...,
-        sort: Callable[[str], SupportsRichComparison] | bool = ...,
+        sort: Callable[[bytes], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[bytes]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
         same_file_system: bool = ...,
         skip_stdout: bool = ...,
-        filter_entry: Callable[[DirEntry], bool] | None = ...,
+        filter_entry: Callable[[DirEntry[bytes]], bool] | None = ...,
         onerror: Callable[[Exception], None] | None = ...,
-    ) -> Walk: ...
+        on_skip: Callable[[bytes, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: None = ...,
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[DirEntry[bytes]]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: StrPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[str], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[str]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[str]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[str, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: type[str],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[str]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: BytesPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[bytes], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[bytes]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[bytes]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[bytes, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: type[str],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[str]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: StrPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[str], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[str]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[str]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[str, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: type[bytes],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[bytes]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: BytesPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[bytes], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[bytes]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[bytes]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[bytes, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: type[bytes],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[bytes]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: StrPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[str], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[str]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[str]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[str, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: Callable[[str], T],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[T]: ...
+    @overload
+    def __new__(
+        cls,
+        *paths: BytesPath,
+        max_depth: int | None = ...,
+        min_depth: int = ...,
+        follow_symlinks: bool = ...,
+        max_filesize: int | None = ...,
+        min_filesize: int | None = ...,
+        modified_after: datetime | float | None = ...,
+        modified_before: datetime | float | None = ...,
+        accessed_before: datetime | float | None = ...,
+        global_ignore_files: Sequence[StrPath] | None = ...,
+        custom_ignore_filenames: Sequence[str] | None = ...,
+        overrides: Overrides | None = ...,
+        types: Types | None = ...,
+        hidden: bool = ...,
+        parents: bool = ...,
+        ignore: bool = ...,
+        git_global: bool = ...,
+        git_ignore: bool = ...,
+        git_exclude: bool = ...,
+        require_git: bool = ...,
+        ignore_case_insensitive: bool = ...,
+        sort: Callable[[bytes], SupportsRichComparison] | SortMode | bool = ...,
+        sort_key: Callable[[DirEntry[bytes]], SupportsRichComparison] | None = ...,
+        reverse: bool = ...,
+        contents_first: bool = ...,
+        order: Literal["dfs", "bfs"] = ...,
+        same_file_system: bool = ...,
+        skip_stdout: bool = ...,
+        filter_entry: Callable[[DirEntry[bytes]], bool] | None = ...,
+        onerror: Callable[[Exception], None] | None = ...,
+        on_skip: Callable[[bytes, Match], object] | None = ...,
+        threads: int | None = ...,
+        path_type: Callable[[bytes], T],
+        prefetch_metadata: bool = ...,
+        unique_inodes: bool = ...,
+        count_skipped: bool = ...,
+        count_bytes: bool = ...,
+    ) -> Walk[T]: ...
     def disable_standard_filters(self) -> None: ...
     def enable_standard_filters(self) -> None: ...
+    def explain(self, path: StrPath) -> Match | None: ...
+    def skip_current_dir(self) -> None: ...
+    def next_batch(self, n: int) -> list[T_co]: ...
+    def batches(self, size: int) -> Iterator[list[T_co]]: ...
+    @property
+    def paths(self) -> list[StrPath | BytesPath]: ...
     @property
-    def paths(self) -> list[StrPath]: ...
+    def stats(self) -> WalkStats: ...
     max_depth: int | None
+    min_depth: int
     follow_symlinks: bool
     max_filesize: int | None
+    min_filesize: int | None
+    @property
+    def modified_after(self) -> float | None: ...
+    @modified_after.setter
+    def modified_after(self, value: datetime | float | None) -> None: ...
+    @property
+    def modified_before(self) -> float | None: ...
+    @modified_before.setter
+    def modified_before(self, value: datetime | float | None) -> None: ...
+    @property
+    def accessed_before(self) -> float | None: ...
+    @accessed_before.setter
+    def accessed_before(self, value: datetime | float | None) -> None: ...
     @property
     def global_ignore_files(self) -> list[StrPath]: ...
     @property
@@ -116,18 +501,43 @@ class Walk:
     git_exclude: bool
     require_git: bool
     ignore_case_insensitive: bool
-    sort: Callable[[str], SupportsRichComparison] | bool
+    sort: Callable[[Any], SupportsRichComparison] | SortMode | bool
+    sort_key: Callable[[DirEntry[Any]], SupportsRichComparison] | None
+    reverse: bool
+    contents_first: bool
+    order: Literal["dfs", "bfs"]
     same_file_system: bool
     skip_stdout: bool
-    filter_entry: Callable[[DirEntry], bool] | None
+    filter_entry: Callable[[DirEntry[Any]], bool] | None
     onerror: Callable[[Exception], None] | None
-    def __enter__(self) -> Walk: ...
+    on_skip: Callable[[Any, Match], object] | None
+    threads: int | None
+    prefetch_metadata: bool
+    unique_inodes: bool
+    count_skipped: bool
+    count_bytes: bool
+    path_type: Callable[[Any], Any] | None
+    def __enter__(self) -> Walk[T_co]: ...
     def __exit__(
         self,
         exc_type: type[BaseException] | None,
         exc_val: BaseException | None,
         exc_tb: TracebackType | None,
     ) -> None: ...
+    async def __aenter__(self) -> Walk[T_co]: ...
+    async def __aexit__(
+        self,
+        exc_type: type[BaseException] | None,
+        exc_val: BaseException | None,
+        exc_tb: TracebackType | None,
+    ) -> None: ...
     def close(self) -> None: ...
-    def __iter__(self) -> Walk: ...
-    def __next__(self) -> DirEntry: ...
+    def __iter__(self) -> Walk[T_co]: ...
+    def __next__(self) -> T_co: ...
+    def __aiter__(self) -> Walk[T_co]: ...
+    def __anext__(self) -> asyncio.Future[T_co]: ...
+
+@overload
+def disk_usage(*paths: StrPath, **walk_options: Any) -> dict[str, DiskUsage]: ...
+@overload
+def disk_usage(*paths: BytesPath, **walk_options: Any) -> dict[bytes, DiskUsage]: ...
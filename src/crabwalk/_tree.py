@@ -0,0 +1,234 @@
+import os
+from collections.abc import Callable, Iterator
+from functools import partial
+from typing import Any, AnyStr, Generic, Optional, Union
+
+from ._lib import DirEntry, Walk
+from ._types import LoopError
+
+WalkTuple = tuple[AnyStr, list[AnyStr], list[AnyStr]]
+
+# Walk options which don't have a meaning when each directory is listed by a
+# separate walk, or which walk_tree sets itself
+_UNSUPPORTED_OPTIONS = (
+    "follow_symlinks",
+    "min_depth",
+    "contents_first",
+    "threads",
+    "unique_inodes",
+    "path_type",
+    "count_skipped",
+    "count_bytes",
+    "order",
+)
+
+
+def walk_tree(
+    top: Union[AnyStr, "os.PathLike[AnyStr]"],
+    topdown: bool = True,
+    onerror: Optional[Callable[[Exception], None]] = None,
+    followlinks: bool = False,
+    **kwargs: Any,
+) -> Iterator[WalkTuple[AnyStr]]:
+    """Generate ``(dirpath, dirnames, filenames)`` tuples like :func:`os.walk`,
+    while applying the same filters as :class:`Walk`.
+
+    When ``topdown`` is true, ``dirnames`` may be modified in place to choose
+    which directories are walked, for example to prune ignored directories.
+    Like :func:`os.walk`, symbolic links to directories are included in
+    ``dirnames`` but only walked if ``followlinks`` is true.
+
+    .. code-block:: python
+
+        for dirpath, dirnames, filenames in walk_tree(".", hidden=False):
+            dirnames[:] = [name for name in dirnames if name != "build"]
+            print(dirpath, filenames)
+
+    :param top: The directory to walk. If it's :class:`bytes`, the yielded paths
+        and names are also bytes.
+    :param topdown: Whether to generate the tuple for a directory before the
+        tuples for its subdirectories.
+    :param onerror: Called with an exception for each error, like the
+        ``onerror`` argument to :class:`Walk`.
+    :param followlinks: Whether to walk symbolic links to directories. File
+        system loops are reported to ``onerror`` as :exc:`LoopError`.
+    :param kwargs: Passed to :class:`Walk`, except that ``max_depth`` limits the
+        depth of the directories listed, and ``parents`` can't be false.
+        ``follow_symlinks`` (use ``followlinks``), ``min_depth``,
+        ``contents_first``, ``threads``, ``unique_inodes``, ``path_type``,
+        ``count_skipped``, ``count_bytes`` and ``order`` aren't supported.
+
+    Each directory is listed by a separate :class:`Walk`, which reads the
+    ignore files of every parent directory again, so walking a deep tree is
+    slower than with :class:`Walk`. The entries passed to ``filter_entry`` and
+    ``sort_key`` wrap a :class:`DirEntry`, replacing its :attr:`~DirEntry.depth`,
+    :attr:`~DirEntry.root` and :attr:`~DirEntry.relative_path` with those it
+    would have when walking ``top``, and the ``depth`` of errors passed to
+    ``onerror`` is adjusted the same way.
+    """
+    for name in _UNSUPPORTED_OPTIONS:
+        if name in kwargs:
+            raise TypeError(f"walk_tree() got an unsupported keyword argument {name!r}")
+    if not kwargs.get("parents", True):
+        raise ValueError("walk_tree doesn't support parents=False")
+    max_depth: Optional[int] = kwargs.pop("max_depth", None)
+    path = os.fspath(top)
+    ancestors: dict[tuple[int, int], AnyStr] = {}
+    if followlinks:
+        key = _file_id(path)
+        if key is not None:
+            ancestors[key] = path
+    yield from _walk_tree(
+        path,
+        None,
+        0,
+        ancestors,
+        top=path,
+        topdown=topdown,
+        onerror=onerror,
+        followlinks=followlinks,
+        max_depth=max_depth,
+        kwargs=kwargs,
+    )
+
+
+def _walk_tree(
+    dirpath: AnyStr,
+    reldir: Optional[AnyStr],
+    depth: int,
+    ancestors: dict[tuple[int, int], AnyStr],
+    *,
+    top: AnyStr,
+    topdown: bool,
+    onerror: Optional[Callable[[Exception], None]],
+    followlinks: bool,
+    max_depth: Optional[int],
+    kwargs: dict[str, Any],
+) -> Iterator[WalkTuple[AnyStr]]:
+    if max_depth is not None and depth >= max_depth:
+        return
+
+    def tree_entry(entry: "DirEntry[AnyStr]") -> "_TreeEntry[AnyStr]":
+        relative_path = entry.relative_path
+        if reldir is not None:
+            relative_path = os.path.join(reldir, relative_path)
+        return _TreeEntry(entry, depth + entry.depth, top, relative_path)
+
+    # Callbacks get the depth relative to top rather than dirpath
+    walk_kwargs = dict(kwargs)
+    filter_entry = kwargs.get("filter_entry")
+    if filter_entry is not None:
+        walk_kwargs["filter_entry"] = lambda entry: filter_entry(tree_entry(entry))
+    sort_key = kwargs.get("sort_key")
+    if sort_key is not None:
+        walk_kwargs["sort_key"] = lambda entry: sort_key(tree_entry(entry))
+    if onerror is not None:
+        walk_kwargs["onerror"] = partial(_report_error, onerror, depth)
+
+    dirnames: list[AnyStr] = []
+    filenames: list[AnyStr] = []
+    # Directories which may be descended into, which excludes symlinks unless
+    # followlinks is true.
+    walk_dirs: set[AnyStr] = set()
+    entry: DirEntry[AnyStr]
+    with Walk(
+        dirpath,
+        max_depth=1,
+        follow_symlinks=followlinks,
+        **walk_kwargs,
+    ) as walk:
+        for entry in walk:
+            if entry.depth == 0:
+                continue
+            if entry.is_dir():
+                dirnames.append(entry.name)
+                walk_dirs.add(entry.name)
+            elif entry.is_symlink() and os.path.isdir(entry.path):
+                # Like os.walk, symlinks to directories are listed as
+                # directories even when they aren't followed.
+                dirnames.append(entry.name)
+            else:
+                filenames.append(entry.name)
+
+    if topdown:
+        yield dirpath, dirnames, filenames
+
+    # When topdown is true, dirnames may have been modified in place to skip
+    # directories.
+    for dirname in dirnames:
+        if dirname not in walk_dirs:
+            continue
+        path = os.path.join(dirpath, dirname)
+        child_ancestors = ancestors
+        if followlinks:
+            key = _file_id(path)
+            if key in ancestors:
+                if onerror is not None:
+                    onerror(
+                        LoopError(
+                            ancestor=os.fsdecode(ancestors[key]),
+                            child=os.fsdecode(path),
+                            depth=depth + 1,
+                        )
+                    )
+                continue
+            if key is not None:
+                child_ancestors = {**ancestors, key: path}
+        yield from _walk_tree(
+            path,
+            dirname if reldir is None else os.path.join(reldir, dirname),
+            depth + 1,
+            child_ancestors,
+            top=top,
+            topdown=topdown,
+            onerror=onerror,
+            followlinks=followlinks,
+            max_depth=max_depth,
+            kwargs=kwargs,
+        )
+
+    if not topdown:
+        yield dirpath, dirnames, filenames
+
+
+class _TreeEntry(Generic[AnyStr]):
+    """A :class:`DirEntry` found by the walk of a single directory, with the
+    depth, root and relative path it has in the tree being walked."""
+
+    def __init__(
+        self,
+        entry: "DirEntry[AnyStr]",
+        depth: int,
+        root: AnyStr,
+        relative_path: AnyStr,
+    ) -> None:
+        self._entry = entry
+        self.depth = depth
+        self.root = root
+        self.relative_path = relative_path
+
+    def __getattr__(self, name: str) -> Any:
+        return getattr(self._entry, name)
+
+    def __fspath__(self) -> AnyStr:
+        return self._entry.path
+
+    def __repr__(self) -> str:
+        return repr(self._entry)
+
+
+def _report_error(
+    onerror: Callable[[Exception], None], depth: int, error: Exception
+) -> None:
+    error_depth = getattr(error, "depth", None)
+    if error_depth is not None:
+        setattr(error, "depth", error_depth + depth)
+    onerror(error)
+
+
+def _file_id(path: Union[str, bytes]) -> Optional[tuple[int, int]]:
+    try:
+        st = os.stat(path)
+    except OSError:
+        return None
+    return st.st_dev, st.st_ino
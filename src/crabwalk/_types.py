@@ -1,7 +1,10 @@
 import os
 import sys
 from collections.abc import Iterable, Iterator, MutableSequence, Sequence
-from typing import NamedTuple, Optional, Union, overload
+from typing import TYPE_CHECKING, NamedTuple, Optional, Union, overload
+
+if TYPE_CHECKING:
+    from ._lib import CompiledOverrides
 
 
 def _display(s: str) -> str:
@@ -92,7 +95,10 @@ class InvalidDefinitionError(WalkError):
         super().__init__()
 
     def __str__(self) -> str:
-        return "invalid definition (format is type:glob, e.g. html:*.html)"
+        return (
+            f"{self._prefix}invalid definition "
+            "(format is type:glob, e.g. html:*.html)"
+        )
 
 
 class UnrecognizedFileTypeError(WalkError):
@@ -121,6 +127,104 @@ class Override(NamedTuple):
     case_insensitive: bool = False
 
 
+class Match(NamedTuple):
+    """A :class:`~collections.namedtuple` describing the rule which decided
+    whether a path is ignored or whitelisted.
+
+    :param kind: The kind of rule which matched: ``"override"``, ``"custom"``,
+        ``"ignore"``, ``"gitignore"``, ``"git_exclude"``, ``"git_global"``,
+        ``"global_ignore"``, ``"types"``, ``"hidden"``, ``"max_filesize"``,
+        ``"min_filesize"``, ``"modified_after"``, ``"modified_before"``,
+        ``"accessed_before"`` or ``"same_file_system"``.
+    :type kind: str
+    :param whitelist: Whether the path was whitelisted rather than ignored.
+    :type whitelist: bool
+    :param glob: The original glob which matched, if any.
+    :type glob: typing.Optional[str]
+    :param path: The file which defined the glob, if any.
+    :type path: typing.Optional[str]
+    :param line: The line number of the glob in :attr:`path`, if known.
+    :type line: typing.Optional[int]
+    :param file_type: The name of the file type definition which matched, if
+        any.
+    :type file_type: typing.Optional[str]
+    """
+
+    kind: str
+    whitelist: bool
+    glob: Optional[str] = None
+    path: Optional[str] = None
+    line: Optional[int] = None
+    file_type: Optional[str] = None
+
+    @property
+    def ignore(self) -> bool:
+        """Whether the path was ignored."""
+        return not self.whitelist
+
+
+class WalkStats(NamedTuple):
+    """A :class:`~collections.namedtuple` of counts accumulated by a
+    :class:`Walk`, returned by :attr:`Walk.stats`.
+
+    :param dirs: The number of directories yielded.
+    :type dirs: int
+    :param files: The number of files yielded.
+    :type files: int
+    :param symlinks: The number of symbolic links yielded. If the walk follows
+        symbolic links, they are also counted as directories or files.
+    :type symlinks: int
+    :param errors: The number of errors encountered.
+    :type errors: int
+    :param skipped_ignored: The number of entries skipped by ignore rules,
+        overrides, types or the size and time filters. Only counted if
+        ``count_skipped`` is set, otherwise ``None``.
+    :type skipped_ignored: typing.Optional[int]
+    :param skipped_hidden: The number of hidden entries skipped. Only counted
+        if ``count_skipped`` is set, otherwise ``None``.
+    :type skipped_hidden: typing.Optional[int]
+    :param bytes: The total size of the files yielded. Only counted if
+        ``count_bytes`` is set, otherwise ``None``.
+    :type bytes: typing.Optional[int]
+    :param elapsed: The number of seconds since the walk started, until it
+        finished or was closed.
+    :type elapsed: float
+    """
+
+    dirs: int
+    files: int
+    symlinks: int
+    errors: int
+    skipped_ignored: Optional[int]
+    skipped_hidden: Optional[int]
+    bytes: Optional[int]
+    elapsed: float
+
+
+class DiskUsage(NamedTuple):
+    """A :class:`~collections.namedtuple` of the totals for a directory,
+    returned by :func:`disk_usage`.
+
+    :param apparent_size: The total size in bytes of the directory and the
+        entries below it.
+    :type apparent_size: int
+    :param allocated_size: The total number of bytes allocated on disk for the
+        directory and the entries below it. This is the same as
+        :attr:`apparent_size` on platforms other than Unix.
+    :type allocated_size: int
+    :param files: The number of entries below the directory which aren't
+        directories.
+    :type files: int
+    :param dirs: The number of directories below the directory.
+    :type dirs: int
+    """
+
+    apparent_size: int
+    allocated_size: int
+    files: int
+    dirs: int
+
+
 def coerce_override(v: object) -> Override:
     if isinstance(v, str):
         v = Override(v)
@@ -229,3 +333,13 @@ class Overrides(MutableSequence[OverrideT]):
     def __repr__(self) -> str:
         cls = type(self)
         return f"{cls.__name__}({self._overrides!r}, path={self._path!r})"
+
+    def compile(self) -> "CompiledOverrides":
+        """Compile the globs into a matcher which can be used without walking
+        a directory.
+
+        :raises GlobError: If a glob is invalid.
+        """
+        from ._lib import CompiledOverrides
+
+        return CompiledOverrides(self)
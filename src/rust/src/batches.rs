@@ -0,0 +1,51 @@
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+
+use crate::Walk;
+
+/// Iterator returned by `Walk.batches`.
+#[pyclass(module = "crabwalk")]
+pub(crate) struct Batches {
+    walk: Option<Py<Walk>>, // Only None after tp_clear
+    size: usize,
+}
+
+impl Batches {
+    pub(crate) fn new(walk: Py<Walk>, size: usize) -> Self {
+        Self {
+            walk: Some(walk),
+            size,
+        }
+    }
+}
+
+#[pymethods]
+impl Batches {
+    fn __iter__(self_: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        self_
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<Vec<PyObject>>> {
+        let walk = match &self.walk {
+            Some(walk) => walk,
+            None => return Ok(None),
+        };
+        let entries = walk.bind(py).try_borrow_mut()?.next_batch(py, self.size)?;
+        Ok(if entries.is_empty() {
+            None
+        } else {
+            Some(entries)
+        })
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        if let Some(walk) = &self.walk {
+            visit.call(walk)?;
+        }
+        Ok(())
+    }
+
+    fn __clear__(&mut self) {
+        self.walk = None;
+    }
+}
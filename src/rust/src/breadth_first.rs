@@ -0,0 +1,296 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::vec;
+
+use same_file::Handle;
+
+use crate::explain::{DirNode, Explainer};
+use crate::sort::Comparator;
+use crate::util::unix_metadata;
+use crate::walker::{io_error, Dent, DentResult, EntryFilter, PathEntry};
+
+/// Yields every entry at one depth before any entry at the next.
+///
+/// `ignore::Walk` is depth first, so this walks with a queue of directories
+/// instead, listing each of them once. The ignore files of a directory are
+/// read when it's yielded, and apply to its children through its `DirNode`,
+/// like the ignore crate's per-directory matchers. Entries are skipped in the
+/// same order as `ignore::Walk` does: ignore rules, `skip_stdout`, then the
+/// entry filter.
+pub(crate) struct BreadthFirst {
+    matcher: Explainer,
+    roots: vec::IntoIter<PathBuf>,
+    dirs: VecDeque<QueuedDir>,
+    // Results found when listing a directory, which are still to be yielded
+    pending: VecDeque<DentResult>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    same_file_system: bool,
+    stdout: Option<Handle>,
+    filter: Option<EntryFilter>,
+    compare: Option<Comparator>,
+}
+
+/// A directory whose children are still to be listed.
+struct QueuedDir {
+    path: PathBuf,
+    depth: usize,
+    node: Arc<DirNode>,
+    // The device of the root, if same_file_system is enabled
+    root_device: Option<u64>,
+}
+
+/// A child of a directory, as listed by `read_dir`.
+struct Child {
+    path: PathBuf,
+    file_type: FileType,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl BreadthFirst {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        matcher: Explainer,
+        roots: Vec<PathBuf>,
+        max_depth: Option<usize>,
+        follow_links: bool,
+        same_file_system: bool,
+        skip_stdout: bool,
+        filter: Option<EntryFilter>,
+        compare: Option<Comparator>,
+    ) -> Self {
+        Self {
+            matcher,
+            roots: roots.into_iter(),
+            dirs: VecDeque::new(),
+            pending: VecDeque::new(),
+            max_depth,
+            follow_links,
+            same_file_system,
+            // Like the walker, don't skip anything if stdout can't be opened
+            stdout: skip_stdout.then(|| Handle::stdout().ok()).flatten(),
+            filter,
+            compare,
+        }
+    }
+
+    fn descends_from(&self, depth: usize) -> bool {
+        self.max_depth.map_or(true, |max_depth| depth < max_depth)
+    }
+
+    /// Yield a root, which is never skipped, and queue it if it's a directory.
+    fn add_root(&mut self, path: PathBuf) {
+        let node = Arc::new(self.matcher.root_node(&path));
+        if let Some(err) = node.parents_error() {
+            self.pending.push_back(Err(err.clone()));
+        }
+        // A root which is a file is followed even if follow_links is false
+        let follow_link = self.follow_links || path.is_file();
+        let (mut entry, _) = match PathEntry::from_path(&path, 0, follow_link) {
+            Ok(entry) => entry,
+            Err(err) => return self.pending.push_back(Err(io_error(err, &path))),
+        };
+        let stat = |path: &Path| path.metadata().map_err(|err| io_error(err, path));
+        let root_device = if self.same_file_system {
+            match stat(&entry.path) {
+                Ok(metadata) => unix_metadata(&metadata).map(|md| md.dev),
+                Err(err) => return self.pending.push_back(Err(err)),
+            }
+        } else {
+            None
+        };
+        // A root which is a symlink to a directory is always descended into
+        let is_dir = if entry.is_symlink && !follow_link {
+            match stat(&entry.path) {
+                Ok(metadata) => metadata.is_dir(),
+                Err(err) => return self.pending.push_back(Err(err)),
+            }
+        } else {
+            entry.file_type.is_dir()
+        };
+        if is_dir {
+            entry.err = node.error().cloned();
+            if self.descends_from(0) {
+                self.dirs.push_back(QueuedDir {
+                    path: entry.path.clone(),
+                    depth: 0,
+                    node,
+                    root_device,
+                });
+            }
+        }
+        self.pending.push_back(Ok(Dent::Path(entry)));
+    }
+
+    /// List the children of a directory, queueing those which are
+    /// directories and should be descended into.
+    fn list(&mut self, dir: QueuedDir) {
+        let read_dir = match fs::read_dir(&dir.path) {
+            Ok(read_dir) => read_dir,
+            Err(err) => return self.pending.push_back(Err(io_error(err, &dir.path))),
+        };
+        let mut children: Vec<_> = read_dir
+            .map(|child| {
+                let child = child.map_err(ignore::Error::Io)?;
+                let path = child.path();
+                match child.file_type() {
+                    Ok(file_type) => Ok(Child {
+                        path,
+                        file_type,
+                        #[cfg(unix)]
+                        ino: std::os::unix::fs::DirEntryExt::ino(&child),
+                    }),
+                    Err(err) => Err(io_error(err, &path)),
+                }
+            })
+            .collect();
+        if let Some(compare) = &self.compare {
+            // Like the walker, errors sort first
+            children.sort_by(|a, b| match (a, b) {
+                (Ok(a), Ok(b)) => compare(&a.path, &b.path),
+                (Ok(_), Err(_)) => Ordering::Greater,
+                (Err(_), Ok(_)) => Ordering::Less,
+                (Err(_), Err(_)) => Ordering::Equal,
+            });
+        }
+        let depth = dir.depth + 1;
+        for child in children {
+            let result = child.and_then(|child| self.visit(&dir, child, depth));
+            match result {
+                Ok(Some(entry)) => self.pending.push_back(Ok(Dent::Path(entry))),
+                Ok(None) => (),
+                Err(err) => self.pending.push_back(Err(err)),
+            }
+        }
+    }
+
+    /// Check a child of `dir`, returning `None` if it's skipped.
+    fn visit(
+        &mut self,
+        dir: &QueuedDir,
+        child: Child,
+        depth: usize,
+    ) -> Result<Option<PathEntry>, ignore::Error> {
+        let entry = if self.follow_links && child.file_type.is_symlink() {
+            let (entry, _) = PathEntry::from_path(&child.path, depth, true)
+                .map_err(|err| io_error(err, &child.path))?;
+            if entry.file_type.is_dir() {
+                check_loop(&entry.path, depth)?;
+            }
+            entry
+        } else {
+            PathEntry {
+                path: child.path,
+                depth,
+                file_type: child.file_type,
+                is_symlink: child.file_type.is_symlink(),
+                follow_link: self.follow_links,
+                #[cfg(unix)]
+                ino: child.ino,
+                err: None,
+            }
+        };
+        let is_dir = entry.file_type.is_dir();
+        if self
+            .matcher
+            .matched(&dir.node, &entry.path, is_dir)
+            .map_or(false, |mat| !mat.whitelist)
+        {
+            return Ok(None);
+        }
+        if let Some(stdout) = &self.stdout {
+            match is_same_file(&entry, stdout) {
+                Ok(true) => return Ok(None),
+                Ok(false) => (),
+                Err(err) => {
+                    return Err(ignore::Error::WithPath {
+                        path: entry.path,
+                        err: Box::new(ignore::Error::Io(err)),
+                    })
+                }
+            }
+        }
+        let dent = Dent::Path(entry);
+        if let Some(filter) = &self.filter {
+            if !filter.accepts(&dent) {
+                return Ok(None);
+            }
+        }
+        let Dent::Path(mut entry) = dent else {
+            unreachable!()
+        };
+        if is_dir {
+            let node = Arc::new(self.matcher.child_node(&entry.path, dir.node.clone()));
+            entry.err = node.error().cloned();
+            let same_device = match dir.root_device {
+                Some(root_device) => {
+                    let metadata = entry.path.metadata();
+                    let metadata = metadata.map_err(|err| io_error(err, &entry.path))?;
+                    unix_metadata(&metadata).map_or(true, |md| md.dev == root_device)
+                }
+                None => true,
+            };
+            if same_device && self.descends_from(depth) {
+                self.dirs.push_back(QueuedDir {
+                    path: entry.path.clone(),
+                    depth,
+                    node,
+                    root_device: dir.root_device,
+                });
+            }
+        }
+        Ok(Some(entry))
+    }
+}
+
+impl Iterator for BreadthFirst {
+    type Item = DentResult;
+
+    fn next(&mut self) -> Option<DentResult> {
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
+            }
+            // Each root is walked to the end before the next one
+            match self.dirs.pop_front() {
+                Some(dir) => self.list(dir),
+                None => {
+                    let root = self.roots.next()?;
+                    self.add_root(root);
+                }
+            }
+        }
+    }
+}
+
+/// Return an error if following a symlink to the directory at `path` leads
+/// back to one of its ancestors under the root.
+fn check_loop(path: &Path, depth: usize) -> Result<(), ignore::Error> {
+    let handle = Handle::from_path(path).map_err(ignore::Error::Io)?;
+    for ancestor in path.ancestors().skip(1).take(depth) {
+        if Handle::from_path(ancestor).map_err(ignore::Error::Io)? == handle {
+            return Err(ignore::Error::WithDepth {
+                depth,
+                err: Box::new(ignore::Error::Loop {
+                    ancestor: ancestor.to_path_buf(),
+                    child: path.to_path_buf(),
+                }),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn is_same_file(entry: &PathEntry, handle: &Handle) -> io::Result<bool> {
+    // Avoid opening every entry when its inode number already differs
+    #[cfg(unix)]
+    if entry.ino != handle.ino() {
+        return Ok(false);
+    }
+    Ok(Handle::from_path(&entry.path)? == *handle)
+}
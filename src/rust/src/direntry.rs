@@ -1,26 +1,126 @@
-use std::ffi::OsStr;
-use std::path::Path;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use pyo3::prelude::*;
 use pyo3::types::IntoPyDict;
 use pyo3::{PyTraverseError, PyVisit};
 
+use crate::error::IntoPyErr;
+use crate::metadata::Metadata;
+use crate::util::path_to_object;
+use crate::walker::{Dent, PathEntry};
 use crate::OS_STAT;
 
 #[pyclass(module = "crabwalk")]
 pub(crate) struct DirEntry {
-    inner: ignore::DirEntry,
+    inner: Dent,
+    roots: Roots,
     #[pyo3(get)]
     follow_symlinks: bool,
+    // Whether path and name are bytes, because the walked paths were bytes
+    as_bytes: bool,
+    // The results of `os.stat` following and not following symlinks
     stat: Option<PyObject>,
+    lstat: Option<PyObject>,
+    // Read when `metadata` is first called, unless the walk prefetched it
+    metadata: Option<fs::Metadata>,
+}
+
+/// The paths being walked, as they were given, so that every entry under a
+/// root reports the same spelling of it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Roots(Arc<[PathBuf]>);
+
+impl Roots {
+    pub(crate) fn new(roots: Vec<PathBuf>) -> Self {
+        Self(roots.into())
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &PathBuf> {
+        self.0.iter()
+    }
+
+    /// The root which a path at the given depth was found under. Removing
+    /// the last `depth` components drops any trailing separator or `.` from
+    /// the root, so look up the root as it was given.
+    pub(crate) fn root_of<'a>(&'a self, path: &'a Path, depth: usize) -> &'a Path {
+        let root = path.ancestors().nth(depth).unwrap_or(path);
+        self.0
+            .iter()
+            .find(|given| given.as_path() == root)
+            .map_or(root, PathBuf::as_path)
+    }
 }
 
 impl DirEntry {
-    pub(crate) fn new(dir_entry: ignore::DirEntry, follow_symlinks: bool) -> Self {
+    pub(crate) fn new(
+        dent: Dent,
+        roots: Roots,
+        follow_symlinks: bool,
+        as_bytes: bool,
+        metadata: Option<fs::Metadata>,
+    ) -> Self {
         Self {
-            inner: dir_entry,
+            inner: dent,
+            roots,
+            follow_symlinks,
+            as_bytes,
+            stat: None,
+            lstat: None,
+            metadata,
+        }
+    }
+
+    /// Create an entry for a path which the walker hasn't yielded yet, with
+    /// the same file type that the walker would report.
+    pub(crate) fn from_path(
+        path: PathBuf,
+        depth: usize,
+        roots: Roots,
+        follow_symlinks: bool,
+        as_bytes: bool,
+    ) -> io::Result<Self> {
+        let (entry, metadata) = PathEntry::from_path(&path, depth, follow_symlinks)?;
+        Ok(Self {
+            inner: Dent::Path(entry),
+            roots,
             follow_symlinks,
+            as_bytes,
             stat: None,
+            lstat: None,
+            metadata: Some(metadata),
+        })
+    }
+
+    /// The root which this entry was found under.
+    fn root_path(&self) -> &Path {
+        self.roots.root_of(self.inner.path(), self.inner.depth())
+    }
+
+    /// The path relative to the root, or `.` for the root itself.
+    fn relative_to_root(&self) -> &Path {
+        match self.inner.path().strip_prefix(self.root_path()) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => Path::new("."),
+        }
+    }
+
+    /// The file type of this entry, or `None` if it's a symlink which isn't
+    /// followed or whose target doesn't exist.
+    fn file_type(&self, follow_symlinks: Option<bool>) -> io::Result<Option<FileType>> {
+        let follow_symlinks = follow_symlinks.unwrap_or(self.follow_symlinks);
+        if follow_symlinks == self.follow_symlinks || !self.inner.path_is_symlink() {
+            Ok(Some(self.inner.file_type().expect("DirEntry is not Stdin")))
+        } else if follow_symlinks {
+            match self.inner.path().metadata() {
+                Ok(metadata) => Ok(Some(metadata.file_type())),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        } else {
+            Ok(None)
         }
     }
 }
@@ -30,13 +130,13 @@ impl DirEntry {
     fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
         // Convert path to a PyString and use repr so the output
         // contains surrogate escapes.
-        let path = self.inner.path().as_os_str().to_object(py);
+        let path = self.path(py)?;
         Ok(format!("<DirEntry {}>", path.bind(py).repr()?))
     }
 
     #[getter]
-    fn path(&self) -> &Path {
-        self.inner.path()
+    fn path(&self, py: Python<'_>) -> PyResult<PyObject> {
+        path_to_object(py, self.inner.path(), self.as_bytes)
     }
 
     #[cfg(unix)]
@@ -46,30 +146,49 @@ impl DirEntry {
 
     #[cfg(not(unix))]
     fn inode(&mut self, py: Python<'_>) -> PyResult<u64> {
-        Ok(self.stat(py)?.getattr(py, "st_ino")?.extract(py)?)
+        Ok(self.stat(py, None)?.getattr(py, "st_ino")?.extract(py)?)
     }
 
-    fn is_dir(&self) -> bool {
-        self.inner
-            .file_type()
-            .expect("DirEntry is not Stdin")
-            .is_dir()
+    #[pyo3(signature = (*, follow_symlinks = None))]
+    fn is_dir(&self, follow_symlinks: Option<bool>) -> PyResult<bool> {
+        Ok(self
+            .file_type(follow_symlinks)?
+            .map_or(false, |file_type| file_type.is_dir()))
     }
 
-    fn is_file(&self) -> bool {
-        self.inner
-            .file_type()
-            .expect("DirEntry is not Stdin")
-            .is_file()
+    #[pyo3(signature = (*, follow_symlinks = None))]
+    fn is_file(&self, follow_symlinks: Option<bool>) -> PyResult<bool> {
+        Ok(self
+            .file_type(follow_symlinks)?
+            .map_or(false, |file_type| file_type.is_file()))
     }
 
     fn is_symlink(&self) -> bool {
         self.inner.path_is_symlink()
     }
 
+    #[cfg(windows)]
+    fn is_junction(&mut self, py: Python<'_>) -> PyResult<bool> {
+        const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+
+        if !self.inner.path_is_symlink() {
+            return Ok(false);
+        }
+        let reparse_tag: u32 = self
+            .stat(py, Some(false))?
+            .getattr(py, "st_reparse_tag")?
+            .extract(py)?;
+        Ok(reparse_tag == IO_REPARSE_TAG_MOUNT_POINT)
+    }
+
+    #[cfg(not(windows))]
+    fn is_junction(&self) -> bool {
+        false
+    }
+
     #[getter]
-    fn name(&self) -> &OsStr {
-        self.inner.file_name()
+    fn name(&self, py: Python<'_>) -> PyResult<PyObject> {
+        path_to_object(py, Path::new(self.inner.file_name()), self.as_bytes)
     }
 
     #[getter]
@@ -77,34 +196,64 @@ impl DirEntry {
         self.inner.depth()
     }
 
-    fn __fspath__(&self) -> &Path {
-        self.inner.path()
+    #[getter]
+    fn root(&self, py: Python<'_>) -> PyResult<PyObject> {
+        path_to_object(py, self.root_path(), self.as_bytes)
     }
 
-    fn stat(&mut self, py: Python<'_>) -> PyResult<PyObject> {
-        match &self.stat {
+    #[getter]
+    fn relative_path(&self, py: Python<'_>) -> PyResult<PyObject> {
+        path_to_object(py, self.relative_to_root(), self.as_bytes)
+    }
+
+    fn __fspath__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.path(py)
+    }
+
+    #[pyo3(signature = (*, follow_symlinks = None))]
+    fn stat(&mut self, py: Python<'_>, follow_symlinks: Option<bool>) -> PyResult<PyObject> {
+        // Following symlinks only makes a difference for symlinks
+        let follow_symlinks =
+            follow_symlinks.unwrap_or(self.follow_symlinks) && self.inner.path_is_symlink();
+        let cached = if follow_symlinks {
+            &mut self.stat
+        } else {
+            &mut self.lstat
+        };
+        match cached {
             Some(stat) => Ok(stat.clone_ref(py)),
             None => {
-                let kwargs = [("follow_symlinks", self.follow_symlinks)];
+                let kwargs = [("follow_symlinks", follow_symlinks)];
                 let stat = OS_STAT
                     .get(py)
                     .unwrap()
                     .bind(py)
                     .call((self.inner.path(),), Some(&kwargs.into_py_dict_bound(py)))?;
-                self.stat = Some(stat.clone().into());
-                Ok(stat.into())
+                Ok(cached.insert(stat.into()).clone_ref(py))
             }
         }
     }
 
+    fn metadata(&mut self, py: Python<'_>) -> PyResult<Metadata> {
+        let metadata = match self.metadata.take() {
+            Some(metadata) => metadata,
+            None => self.inner.metadata().map_err(|err| err.into_py_err(py))?,
+        };
+        Ok(Metadata::new(self.metadata.insert(metadata)))
+    }
+
     fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
         if let Some(stat) = &self.stat {
             visit.call(stat)?;
         }
+        if let Some(lstat) = &self.lstat {
+            visit.call(lstat)?;
+        }
         Ok(())
     }
 
     fn __clear__(&mut self) {
         self.stat = None;
+        self.lstat = None;
     }
 }
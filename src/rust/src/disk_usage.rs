@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+use crate::util::{path_to_object, unix_metadata};
+use crate::walker::Entry;
+use crate::{Walk, TYPES_MODULE};
+
+/// The totals for a directory, converted to a `DiskUsage` from `_types.py`.
+#[derive(Clone, Copy, Debug, Default)]
+struct Usage {
+    apparent_size: u64,
+    allocated_size: u64,
+    files: u64,
+    dirs: u64,
+}
+
+impl Usage {
+    fn to_object(self, py: Python<'_>) -> PyResult<PyObject> {
+        let types_module = TYPES_MODULE.get(py).unwrap().bind(py);
+        let usage = types_module.getattr("DiskUsage")?.call1((
+            self.apparent_size,
+            self.allocated_size,
+            self.files,
+            self.dirs,
+        ))?;
+        Ok(usage.unbind())
+    }
+}
+
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    // Without the number of blocks, this is the apparent size
+    unix_metadata(metadata).map_or(metadata.len(), |md| md.blocks * 512)
+}
+
+/// Walk options which are rejected, because the order of entries doesn't
+/// change the totals, the metadata is always read and the walk's stats aren't
+/// returned.
+const UNSUPPORTED_OPTIONS: [&str; 8] = [
+    "sort",
+    "sort_key",
+    "reverse",
+    "contents_first",
+    "order",
+    "prefetch_metadata",
+    "count_skipped",
+    "count_bytes",
+];
+
+/// Add an entry to the totals of its directory and each parent directory up
+/// to the root. A root which isn't a directory gets its own totals. The keys
+/// are normalized, so that a root given as `dir/` has the key `dir` like the
+/// parent of its entries.
+fn add_entry(totals: &mut BTreeMap<PathBuf, Usage>, entry: &Entry) {
+    let is_dir = entry.file_type().map_or(false, |ft| ft.is_dir());
+    let (apparent_size, allocated_size) = entry.metadata.as_ref().map_or((0, 0), |metadata| {
+        (metadata.len(), allocated_size(metadata))
+    });
+    let skip = if is_dir || entry.depth() == 0 { 0 } else { 1 };
+    for (i, dir) in entry
+        .path()
+        .ancestors()
+        .enumerate()
+        .skip(skip)
+        .take(entry.depth() + 1 - skip)
+    {
+        let usage = totals
+            .entry(dir.components().as_path().to_path_buf())
+            .or_default();
+        usage.apparent_size += apparent_size;
+        usage.allocated_size += allocated_size;
+        if !is_dir {
+            usage.files += 1;
+        } else if i > 0 {
+            usage.dirs += 1;
+        }
+    }
+}
+
+/// Compute the total size of each directory which is walked
+#[pyfunction]
+#[pyo3(signature = (*paths, **walk_options))]
+pub(crate) fn disk_usage(
+    py: Python<'_>,
+    paths: &Bound<'_, PyTuple>,
+    walk_options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<PyObject> {
+    let kwargs = PyDict::new_bound(py);
+    kwargs.set_item("threads", 0)?;
+    if let Some(walk_options) = walk_options {
+        for name in UNSUPPORTED_OPTIONS {
+            if walk_options.contains(name)? {
+                return Err(PyTypeError::new_err(format!(
+                    "disk_usage() got an unsupported keyword argument '{name}'"
+                )));
+            }
+        }
+        kwargs.update(walk_options.as_mapping())?;
+    }
+    let walk = py.get_type_bound::<Walk>().call(paths, Some(&kwargs))?;
+    let mut walk = walk.downcast_into::<Walk>()?.borrow_mut();
+    // Read the metadata on the walker's threads
+    walk.prefetch_metadata = true;
+
+    let mut totals = BTreeMap::new();
+    let result = walk.for_each_entry(py, |entry| add_entry(&mut totals, entry));
+    walk.close(py);
+    result?;
+
+    let usage = PyDict::new_bound(py);
+    for (dir, dir_usage) in totals {
+        usage.set_item(
+            path_to_object(py, &dir, walk.as_bytes)?,
+            dir_usage.to_object(py)?,
+        )?;
+    }
+    Ok(usage.unbind().into())
+}
@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ignore::gitignore::{self, Gitignore, GitignoreBuilder};
+
+use crate::matched::{LineCache, MatchInfo};
+use crate::metadata_filter::MetadataFilter;
+use crate::overrides::OverrideGlobs;
+use crate::util::unix_metadata;
+use crate::walker::Dent;
+
+/// Reproduces the matching done by `ignore::Walk` for a single path, keeping
+/// track of the rule responsible for the outcome.
+///
+/// The ignore crate's per-directory matcher is private, so this mirrors its
+/// precedence rules using the public matchers.
+pub(crate) struct Explainer {
+    pub overrides: Option<OverrideGlobs>,
+    pub types: Option<ignore::types::Types>,
+    pub custom_ignore_filenames: Vec<OsString>,
+    pub global_ignores: Vec<Gitignore>,
+    pub git_global_matcher: Gitignore,
+    pub follow_symlinks: bool,
+    pub same_file_system: bool,
+    pub metadata_filter: MetadataFilter,
+    pub hidden: bool,
+    pub parents: bool,
+    pub ignore: bool,
+    pub git_global: bool,
+    pub git_ignore: bool,
+    pub git_exclude: bool,
+    pub require_git: bool,
+    pub ignore_case_insensitive: bool,
+    // Whether matches include the line of the glob and the file type's glob,
+    // which aren't needed to decide whether a path is ignored
+    pub details: bool,
+    pub dirs: DirCache,
+    pub lines: LineCache,
+}
+
+/// The ignore files found in a single directory.
+struct DirMatchers {
+    custom: Gitignore,
+    ignore: Gitignore,
+    git_ignore: Gitignore,
+    git_exclude: Gitignore,
+    has_git: bool,
+    // Errors reading the ignore files, which the walker reports with the
+    // directory's entry
+    error: Option<ignore::Error>,
+}
+
+/// The matchers of the parents of a root, which apply if `parents` is
+/// enabled, and the canonical path of the root to match against them.
+#[derive(Default)]
+struct AbsoluteMatchers {
+    base: Option<PathBuf>,
+    matchers: Vec<DirMatchers>,
+    error: Option<ignore::Error>,
+}
+
+/// The matchers which apply to the children of a directory: its own, and
+/// through `parent`, those of each directory up to the root.
+pub(crate) struct DirNode {
+    matchers: DirMatchers,
+    parent: Option<Arc<DirNode>>,
+    absolute: Arc<AbsoluteMatchers>,
+}
+
+impl DirNode {
+    /// The matchers of this directory and its parents, nearest first.
+    fn stack(&self) -> impl Iterator<Item = &DirMatchers> {
+        iter::successors(Some(self), |node| node.parent.as_deref()).map(|node| &node.matchers)
+    }
+
+    /// The error reading this directory's ignore files, if any.
+    pub fn error(&self) -> Option<&ignore::Error> {
+        self.matchers.error.as_ref()
+    }
+
+    /// The error reading the ignore files of the parents of the root, if any.
+    pub fn parents_error(&self) -> Option<&ignore::Error> {
+        self.absolute.error.as_ref()
+    }
+}
+
+/// The nodes of the directories whose children are still to be yielded, so
+/// that each directory's ignore files are only read once, like the walker
+/// does.
+#[derive(Default)]
+pub(crate) struct DirCache(Mutex<HashMap<PathBuf, CachedDir>>);
+
+struct CachedDir {
+    node: Arc<DirNode>,
+    // The number of child directories still to be yielded, or None if the
+    // directory itself is still to be yielded, because it was needed by a
+    // child first, e.g. with contents_first
+    pending: Option<usize>,
+}
+
+impl DirCache {
+    /// Take the node of a directory which was needed by its children before
+    /// it was yielded.
+    fn take(&self, dir: &Path) -> Option<Arc<DirNode>> {
+        let mut dirs = self.0.lock().unwrap();
+        match dirs.get(dir) {
+            Some(cached) if cached.pending.is_none() => dirs.remove(dir).map(|cached| cached.node),
+            _ => None,
+        }
+    }
+
+    /// Get the node of a directory for one of its children, dropping it once
+    /// every child directory has had it.
+    fn get_for_child(&self, dir: &Path) -> Option<Arc<DirNode>> {
+        let mut dirs = self.0.lock().unwrap();
+        let cached = dirs.get_mut(dir)?;
+        let node = cached.node.clone();
+        if let Some(pending) = &mut cached.pending {
+            *pending -= 1;
+            if *pending == 0 {
+                dirs.remove(dir);
+            }
+        }
+        Some(node)
+    }
+
+    fn insert(&self, dir: &Path, node: Arc<DirNode>, pending: Option<usize>) {
+        let cached = CachedDir { node, pending };
+        self.0.lock().unwrap().insert(dir.to_path_buf(), cached);
+    }
+}
+
+impl Explainer {
+    /// Explain why `root.join(relative)` would or wouldn't be yielded when
+    /// walking `root`.
+    pub fn explain(&self, root: &Path, relative: &Path) -> Option<MatchInfo> {
+        let mut node = Arc::new(self.root_node(root));
+        let mut path = root.to_path_buf();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            path.push(component);
+            let is_last = components.peek().is_none();
+            let is_dir = !is_last || self.is_dir(&path);
+            let mat = self.matched(&node, &path, is_dir);
+            if is_last {
+                if mat.as_ref().map_or(true, |mat| mat.whitelist) && !is_dir {
+                    if let Some(kind) = self
+                        .metadata(&path)
+                        .and_then(|md| self.metadata_filter.rejects(&md))
+                    {
+                        return Some(MatchInfo::new(kind, false));
+                    }
+                }
+                return mat;
+            }
+            if mat.as_ref().map_or(false, |mat| !mat.whitelist) {
+                // An ignored directory is never descended into
+                return mat;
+            }
+            node = Arc::new(self.child_node(&path, node));
+        }
+        None
+    }
+
+    /// Find the entries which the walker skips after yielding `dent`: the
+    /// children which are ignored or filtered out, or the directory itself if
+    /// it isn't descended into because it's on a different file system.
+    ///
+    /// This lists the directory again, because the walker doesn't report the
+    /// entries it skips, but the ignore files of each directory are only read
+    /// once.
+    pub fn skipped(&self, dent: &Dent, max_depth: Option<usize>) -> Vec<(PathBuf, MatchInfo)> {
+        let path = dent.path();
+        let depth = dent.depth();
+        // A root which is a symlink is followed even if follow_symlinks is false
+        let is_dir =
+            dent.file_type().map_or(false, |ft| ft.is_dir()) || depth == 0 && path.is_dir();
+        if !is_dir || max_depth.map_or(false, |max_depth| depth >= max_depth) {
+            return Vec::new();
+        }
+        let root = path.ancestors().nth(depth).unwrap_or(path);
+        if self.same_file_system && depth > 0 && !same_device(root, path) {
+            // The parent counted this directory as one to be yielded
+            self.parent_node(path, depth);
+            return vec![(
+                path.to_path_buf(),
+                MatchInfo::new("same_file_system", false),
+            )];
+        }
+
+        let (node, children_yielded) = match self.dirs.take(path) {
+            Some(node) => (node, true),
+            None => (self.dir_node(path, depth), false),
+        };
+        let descend = max_depth.map_or(true, |max_depth| depth + 1 < max_depth);
+        let mut child_dirs = 0;
+        // Errors reading the directory are reported by the walker
+        let mut skipped: Vec<_> = fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .filter_map(|child| {
+                let child = child.ok()?;
+                let child_path = child.path();
+                let is_dir = match child.file_type() {
+                    Ok(ft) if ft.is_symlink() && self.follow_symlinks => self.is_dir(&child_path),
+                    Ok(ft) => ft.is_dir(),
+                    Err(_) => false,
+                };
+                let mat = match self.matched(&node, &child_path, is_dir) {
+                    Some(mat) if !mat.whitelist => mat,
+                    _ if is_dir => {
+                        child_dirs += 1;
+                        return None;
+                    }
+                    _ if self.metadata_filter.is_empty() => return None,
+                    _ => MatchInfo::new(
+                        self.metadata(&child_path)
+                            .and_then(|md| self.metadata_filter.rejects(&md))?,
+                        false,
+                    ),
+                };
+                Some((child_path, mat))
+            })
+            .collect();
+        if !children_yielded && descend && child_dirs > 0 {
+            self.dirs.insert(path, node, Some(child_dirs));
+        }
+        skipped.sort_by(|(a, _), (b, _)| a.cmp(b));
+        skipped
+    }
+
+    /// Build the node of a directory yielded by the walker at `depth`, reusing
+    /// the node of its parent.
+    fn dir_node(&self, dir: &Path, depth: usize) -> Arc<DirNode> {
+        if depth == 0 {
+            Arc::new(self.root_node(dir))
+        } else {
+            Arc::new(self.child_node(dir, self.parent_node(dir, depth)))
+        }
+    }
+
+    /// The node of the parent of a directory yielded by the walker at `depth`.
+    /// Directories are usually yielded before their children, otherwise the
+    /// parent is built and kept until it's yielded.
+    fn parent_node(&self, dir: &Path, depth: usize) -> Arc<DirNode> {
+        let parent = dir.parent().unwrap_or(dir);
+        self.dirs.get_for_child(parent).unwrap_or_else(|| {
+            let node = self.dir_node(parent, depth - 1);
+            self.dirs.insert(parent, node.clone(), None);
+            node
+        })
+    }
+
+    pub fn root_node(&self, root: &Path) -> DirNode {
+        DirNode {
+            matchers: self.dir_matchers(root, false),
+            parent: None,
+            absolute: Arc::new(self.absolute_matchers(root)),
+        }
+    }
+
+    pub fn child_node(&self, dir: &Path, parent: Arc<DirNode>) -> DirNode {
+        DirNode {
+            matchers: self.dir_matchers(dir, false),
+            absolute: parent.absolute.clone(),
+            parent: Some(parent),
+        }
+    }
+
+    fn absolute_matchers(&self, root: &Path) -> AbsoluteMatchers {
+        if root.is_dir() && (self.parents || self.git_ignore || self.git_exclude || self.git_global)
+        {
+            if let Ok(base) = root.canonicalize() {
+                let matchers: Vec<_> = base
+                    .ancestors()
+                    .skip(1)
+                    .map(|parent| self.dir_matchers(parent, true))
+                    .collect();
+                let error = combine_errors(
+                    // The ignore crate reads them from the root down
+                    matchers.iter().rev().filter_map(|dir| dir.error.clone()),
+                );
+                return AbsoluteMatchers {
+                    base: Some(base),
+                    matchers,
+                    error,
+                };
+            }
+        }
+        AbsoluteMatchers::default()
+    }
+
+    fn metadata(&self, path: &Path) -> Option<fs::Metadata> {
+        if self.follow_symlinks {
+            fs::metadata(path).ok()
+        } else {
+            fs::symlink_metadata(path).ok()
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).map_or(false, |md| md.is_dir())
+    }
+
+    fn dir_matchers(&self, dir: &Path, is_absolute_parent: bool) -> DirMatchers {
+        let has_git = if is_absolute_parent {
+            self.require_git && self.git_ignore && dir.join(".git").exists()
+        } else {
+            self.require_git
+                && (self.git_ignore || self.git_exclude)
+                && dir.join(".git").metadata().is_ok()
+        };
+        let mut errors = Vec::new();
+        let mut create_gitignore = |dir_for_ignorefile: &Path, names: &[&OsStr]| {
+            let (gitignore, error) = self.create_gitignore(dir, dir_for_ignorefile, names);
+            errors.extend(error);
+            gitignore
+        };
+        let custom_names: Vec<_> = self
+            .custom_ignore_filenames
+            .iter()
+            .map(OsString::as_os_str)
+            .collect();
+        let custom = create_gitignore(dir, &custom_names);
+        let ignore = if self.ignore {
+            create_gitignore(dir, &[OsStr::new(".ignore")])
+        } else {
+            Gitignore::empty()
+        };
+        let git_ignore = if self.git_ignore {
+            create_gitignore(dir, &[OsStr::new(".gitignore")])
+        } else {
+            Gitignore::empty()
+        };
+        let git_dir = dir.join(".git");
+        let git_exclude = if self.git_exclude && !git_dir.is_file() {
+            create_gitignore(&git_dir, &[OsStr::new("info/exclude")])
+        } else {
+            Gitignore::empty()
+        };
+        DirMatchers {
+            custom,
+            ignore,
+            git_ignore,
+            git_exclude,
+            has_git,
+            error: combine_errors(errors),
+        }
+    }
+
+    /// Build the matcher for the ignore files `names` in `dir_for_ignorefile`,
+    /// returning the errors the walker would report for them.
+    fn create_gitignore(
+        &self,
+        dir: &Path,
+        dir_for_ignorefile: &Path,
+        names: &[&OsStr],
+    ) -> (Gitignore, Option<ignore::Error>) {
+        let mut builder = GitignoreBuilder::new(dir);
+        builder
+            .case_insensitive(self.ignore_case_insensitive)
+            .unwrap();
+        let mut errors = Vec::new();
+        for name in names {
+            let path = dir_for_ignorefile.join(name);
+            if path.exists() {
+                // Like the walker, ignore files which can't be read
+                errors.extend(builder.add(path).filter(|err| !err.is_io()));
+            }
+        }
+        match builder.build() {
+            Ok(gitignore) => (gitignore, combine_errors(errors)),
+            Err(err) => {
+                errors.push(err);
+                (Gitignore::empty(), combine_errors(errors))
+            }
+        }
+    }
+
+    /// Convert a gitignore match, finding the line of the glob if `details`
+    /// is set.
+    fn gitignore_match(
+        &self,
+        kind: &'static str,
+        mat: ignore::Match<&gitignore::Glob>,
+    ) -> Option<MatchInfo> {
+        if self.details {
+            self.lines.gitignore_match(kind, mat)
+        } else {
+            MatchInfo::from_gitignore_with(kind, mat, |_, _| None)
+        }
+    }
+
+    fn has_any_ignore_rules(&self) -> bool {
+        self.ignore
+            || self.git_global
+            || self.git_ignore
+            || self.git_exclude
+            || !self.custom_ignore_filenames.is_empty()
+            || !self.global_ignores.is_empty()
+    }
+
+    pub fn matched(&self, node: &DirNode, path: &Path, is_dir: bool) -> Option<MatchInfo> {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        // Overrides have the highest precedence, whether they ignore or
+        // whitelist the path.
+        if let Some(overrides) = &self.overrides {
+            if let Some(mat) = overrides.matched(path, is_dir) {
+                return Some(mat);
+            }
+        }
+        let mut whitelisted = None;
+        if self.has_any_ignore_rules() {
+            match self.matched_ignore(node, path, is_dir) {
+                Some(mat) if !mat.whitelist => return Some(mat),
+                Some(mat) => whitelisted = Some(mat),
+                None => (),
+            }
+        }
+        if let Some(types) = self.types.as_ref().filter(|types| !types.is_empty()) {
+            let mat = types.matched(path, is_dir);
+            let mat = if self.details {
+                MatchInfo::from_types(mat, path)
+            } else {
+                (!mat.is_none()).then(|| MatchInfo::new("types", mat.is_whitelist()))
+            };
+            match mat {
+                Some(mat) if !mat.whitelist => return Some(mat),
+                Some(mat) => whitelisted = Some(mat),
+                None => (),
+            }
+        }
+        if whitelisted.is_none() && self.hidden && is_hidden(path) {
+            return Some(MatchInfo::new("hidden", false));
+        }
+        whitelisted
+    }
+
+    fn matched_ignore(&self, node: &DirNode, path: &Path, is_dir: bool) -> Option<MatchInfo> {
+        let mut m_custom = None;
+        let mut m_ignore = None;
+        let mut m_git_ignore = None;
+        let mut m_git_exclude = None;
+
+        let absolute = &node.absolute;
+        let any_git = !self.require_git
+            || node
+                .stack()
+                .chain(&absolute.matchers)
+                .any(|dir| dir.has_git);
+        let mut saw_git = false;
+        let mut visit = |dir: &DirMatchers, path: &Path| {
+            if m_custom.is_none() {
+                m_custom = self.gitignore_match("custom", dir.custom.matched(path, is_dir));
+            }
+            if m_ignore.is_none() {
+                m_ignore = self.gitignore_match("ignore", dir.ignore.matched(path, is_dir));
+            }
+            if any_git && !saw_git && m_git_ignore.is_none() {
+                m_git_ignore =
+                    self.gitignore_match("gitignore", dir.git_ignore.matched(path, is_dir));
+            }
+            if any_git && !saw_git && m_git_exclude.is_none() {
+                m_git_exclude =
+                    self.gitignore_match("git_exclude", dir.git_exclude.matched(path, is_dir));
+            }
+            saw_git = saw_git || dir.has_git;
+        };
+
+        for dir in node.stack() {
+            visit(dir, path);
+        }
+        if self.parents {
+            if let Some(base) = &absolute.base {
+                let path = base.join(path);
+                for dir in &absolute.matchers {
+                    visit(dir, &path);
+                }
+            }
+        }
+
+        let m_global_ignore = self
+            .global_ignores
+            .iter()
+            .rev()
+            .find_map(|gi| self.gitignore_match("global_ignore", gi.matched(path, is_dir)));
+        let m_git_global = if any_git {
+            self.gitignore_match("git_global", self.git_global_matcher.matched(path, is_dir))
+        } else {
+            None
+        };
+
+        m_custom
+            .or(m_ignore)
+            .or(m_git_ignore)
+            .or(m_git_exclude)
+            .or(m_git_global)
+            .or(m_global_ignore)
+    }
+}
+
+fn same_device(a: &Path, b: &Path) -> bool {
+    let dev = |path: &Path| Some(unix_metadata(&path.metadata().ok()?)?.dev);
+    // Without device numbers, directories on other file systems aren't reported
+    match (dev(a), dev(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// Combine errors like the ignore crate does, into a `Partial` error if there
+/// is more than one.
+fn combine_errors(errors: impl IntoIterator<Item = ignore::Error>) -> Option<ignore::Error> {
+    let mut errors: Vec<_> = errors.into_iter().collect();
+    match errors.len() {
+        0 => None,
+        1 => errors.pop(),
+        _ => Some(ignore::Error::Partial(errors)),
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map_or(false, |name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Split `path` into one of `roots` and the path relative to it.
+pub(crate) fn split_root<'a>(roots: &'a [OsString], path: &Path) -> Option<(&'a Path, PathBuf)> {
+    for root in roots {
+        let root = Path::new(root);
+        if let Ok(relative) = path.strip_prefix(root) {
+            return Some((root, relative.to_path_buf()));
+        }
+    }
+    // Fall back to comparing canonical paths, e.g. for an absolute path inside
+    // a relative root.
+    let path = path.canonicalize().ok()?;
+    for root in roots {
+        let root = Path::new(root);
+        if let Some(relative) = root
+            .canonicalize()
+            .ok()
+            .and_then(|base| path.strip_prefix(base).ok().map(Path::to_path_buf))
+        {
+            return Some((root, relative));
+        }
+    }
+    None
+}
@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::GitignoreBuilder;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::error::IntoPyErr;
+use crate::matched::MatchInfo;
+use crate::util::fspath;
+
+/// A matcher for the globs in one or more gitignore files, which can be used
+/// without walking a directory.
+#[pyclass(module = "crabwalk")]
+pub struct Gitignore {
+    builder: GitignoreBuilder,
+    // Built lazily and discarded when the builder changes
+    matcher: Option<ignore::gitignore::Gitignore>,
+}
+
+#[pymethods]
+impl Gitignore {
+    #[new]
+    #[pyo3(signature = (path, *, case_insensitive = false))]
+    fn new(path: &Bound<'_, PyAny>, case_insensitive: bool) -> PyResult<Self> {
+        let py = path.py();
+        let path: PathBuf = fspath(path)?.extract()?;
+        let mut builder = GitignoreBuilder::new(path);
+        builder
+            .case_insensitive(case_insensitive)
+            .map_err(|err| err.into_py_err(py))?;
+        Ok(Self {
+            builder,
+            matcher: None,
+        })
+    }
+
+    /// Add each glob from the file at ``path``.
+    fn add(&mut self, path: &Bound<'_, PyAny>) -> PyResult<()> {
+        let py = path.py();
+        let path: PathBuf = fspath(path)?.extract()?;
+        self.matcher = None;
+        match self.builder.add(path) {
+            Some(err) => Err(err.into_py_err(py)),
+            None => Ok(()),
+        }
+    }
+
+    /// Add a line from a gitignore file.
+    #[pyo3(signature = (line, *, source = None))]
+    fn add_line(
+        &mut self,
+        py: Python<'_>,
+        line: &str,
+        source: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        let source: Option<PathBuf> = source.map(|p| fspath(p)?.extract()).transpose()?;
+        self.matcher = None;
+        self.builder
+            .add_line(source, line)
+            .map_err(|err| err.into_py_err(py))?;
+        Ok(())
+    }
+
+    #[getter]
+    fn path(&mut self, py: Python<'_>) -> PyResult<PathBuf> {
+        Ok(self.matcher(py)?.path().to_path_buf())
+    }
+
+    #[getter]
+    fn num_ignores(&mut self, py: Python<'_>) -> PyResult<u64> {
+        Ok(self.matcher(py)?.num_ignores())
+    }
+
+    #[getter]
+    fn num_whitelists(&mut self, py: Python<'_>) -> PyResult<u64> {
+        Ok(self.matcher(py)?.num_whitelists())
+    }
+
+    /// Return the glob which matched ``path``, or ``None``.
+    #[pyo3(signature = (path, is_dir = false))]
+    fn matched(
+        &mut self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        is_dir: bool,
+    ) -> PyResult<PyObject> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let matcher = self.matcher(py)?;
+        match MatchInfo::from_gitignore("gitignore", matcher.matched(path, is_dir)) {
+            Some(mat) => mat.into_py(py),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Return the glob which matched ``path`` or any of its parent
+    /// directories, or ``None``.
+    #[pyo3(signature = (path, is_dir = false))]
+    fn matched_path_or_any_parents(
+        &mut self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        is_dir: bool,
+    ) -> PyResult<PyObject> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let matcher = self.matcher(py)?;
+        if !is_under_root(matcher.path(), &path) {
+            return Err(PyValueError::new_err(
+                "path must be relative or inside the gitignore path",
+            ));
+        }
+        let mat = matcher.matched_path_or_any_parents(path, is_dir);
+        match MatchInfo::from_gitignore("gitignore", mat) {
+            Some(mat) => mat.into_py(py),
+            None => Ok(py.None()),
+        }
+    }
+}
+
+impl Gitignore {
+    fn matcher(&mut self, py: Python<'_>) -> PyResult<&ignore::gitignore::Gitignore> {
+        if self.matcher.is_none() {
+            let matcher = self.builder.build().map_err(|err| err.into_py_err(py))?;
+            self.matcher = Some(matcher);
+        }
+        Ok(self.matcher.as_ref().unwrap())
+    }
+}
+
+/// `Gitignore::matched_path_or_any_parents` panics unless this is true.
+fn is_under_root(root: &Path, path: &Path) -> bool {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    !path.has_root() || (root != Path::new(".") && path.starts_with(root))
+}
@@ -1,39 +1,68 @@
 #![deny(rust_2018_idioms)]
 
-use std::cmp::Ordering;
 use std::ffi::OsString;
-use std::path::Path;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::ptr;
 
-use ignore::overrides::OverrideBuilder;
-use ignore::types::TypesBuilder;
-use pyo3::exceptions::{PyException, PyRuntimeError, PyTypeError};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use pyo3::exceptions::{
+    PyBaseException, PyRuntimeError, PyStopAsyncIteration, PyTypeError, PyValueError,
+};
 use pyo3::prelude::*;
 use pyo3::pybacked::PyBackedStr;
 use pyo3::sync::GILOnceCell;
-use pyo3::types::{PyList, PySequence, PyString, PyTraceback, PyTuple, PyType};
+use pyo3::types::{
+    PyBytes, PyCFunction, PyList, PySequence, PyString, PyTraceback, PyTuple, PyType,
+};
 use pyo3::{ffi, PyTraverseError, PyTypeInfo, PyVisit};
 
-use crate::direntry::DirEntry;
+use crate::batches::Batches;
+use crate::breadth_first::BreadthFirst;
+use crate::direntry::{DirEntry, Roots};
 use crate::error::IntoPyErr;
-use crate::types::{Selection, Types};
-use crate::util::{fspath, fspath_list};
-
+use crate::explain::{split_root, DirCache, Explainer};
+use crate::matched::{LineCache, MatchInfo};
+use crate::metadata::Metadata;
+use crate::metadata_filter::{extract_timestamp, MetadataFilter};
+use crate::overrides::{build_overrides, CompiledOverrides, OverrideGlobs};
+use crate::sort::{Comparator, KeyArg, KeyComparator, SortMode};
+use crate::stats::Stats;
+use crate::types::Types;
+use crate::util::{fspath, fspath_list, fspath_list_bytes, path_to_bytes, path_to_object};
+use crate::walker::{
+    CallbackError, ContentsFirst, Dent, Entry, EntryFilter, IgnoreResult, Poll, Prefetch,
+    SeenInodes, SequentialWalker, SkipDir, ThreadedWalker, Waker, WalkResult, Walker,
+};
+
+mod batches;
+mod breadth_first;
 mod direntry;
+mod disk_usage;
 mod error;
+mod explain;
+mod gitignore;
+mod matched;
+mod metadata;
+mod metadata_filter;
+mod overrides;
+mod sort;
+mod stats;
 mod types;
 mod util;
+mod walker;
 
 static TYPES_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
 static KEYS_VIEW_TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 static ITEMS_VIEW_TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 static VALUES_VIEW_TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 static OS_STAT: GILOnceCell<PyObject> = GILOnceCell::new();
+static GET_RUNNING_LOOP: GILOnceCell<PyObject> = GILOnceCell::new();
 
 enum State {
     Unopened,
     Opened,
-    Started(Box<ignore::Walk>),
+    Started(Walker),
     Closed,
 }
 
@@ -42,8 +71,9 @@ pub struct Walk {
     state: State,
     paths: Option<Py<PyList>>, // Only None after tp_clear
     max_depth: Option<usize>,
+    min_depth: usize,
     follow_symlinks: bool,
-    max_filesize: Option<u64>,
+    metadata_filter: MetadataFilter,
     global_ignore_files: Option<Py<PyList>>, // Only None after tp_clear
     custom_ignore_filenames: Option<Py<PyList>>, // Only None after tp_clear
     overrides: Option<PyObject>,
@@ -57,10 +87,36 @@ pub struct Walk {
     require_git: bool,
     ignore_case_insensitive: bool,
     sort: Option<PyObject>,
+    sort_key: Option<PyObject>,
+    reverse: bool,
+    contents_first: bool,
+    order: Order,
     same_file_system: bool,
     skip_stdout: bool,
     filter_entry: Option<PyObject>,
     onerror: Option<PyObject>,
+    on_skip: Option<PyObject>,
+    threads: Option<usize>,
+    path_type: Option<PyObject>,
+    path_kind: PathKind,
+    prefetch_metadata: bool,
+    unique_inodes: bool,
+    count_skipped: bool,
+    count_bytes: bool,
+    // Whether the walked paths are bytes, set when the walk starts
+    as_bytes: bool,
+    // The walked paths, set when the walk starts
+    roots: Roots,
+    callback_error: CallbackError,
+    stats: Stats,
+    // Finds the entries to report to on_skip, set when the walk starts
+    explainer: Option<Explainer>,
+    skip_dir: SkipDir,
+    // The directory skip_current_dir would skip, based on the last entry
+    current_dir: Option<PathBuf>,
+    // An error result which ended the previous batch, so that it's reported
+    // in order
+    peeked: Option<WalkResult>,
 }
 
 #[pymethods]
@@ -70,8 +126,13 @@ impl Walk {
         signature = (
             *paths,
             max_depth = None,
+            min_depth = 0,
             follow_symlinks = false,
             max_filesize = None,
+            min_filesize = None,
+            modified_after = None,
+            modified_before = None,
+            accessed_before = None,
             global_ignore_files = None,
             custom_ignore_filenames = None,
             overrides = None,
@@ -85,10 +146,21 @@ impl Walk {
             require_git = true,
             ignore_case_insensitive = false,
             sort = None,
+            sort_key = None,
+            reverse = false,
+            contents_first = false,
+            order = Order::Dfs,
             same_file_system = false,
             skip_stdout = false,
             filter_entry = None,
-            onerror = None
+            onerror = None,
+            on_skip = None,
+            threads = None,
+            path_type = None,
+            prefetch_metadata = false,
+            unique_inodes = false,
+            count_skipped = false,
+            count_bytes = false
         )
     )]
     #[allow(clippy::too_many_arguments)]
@@ -96,8 +168,13 @@ impl Walk {
         py: Python<'py>,
         paths: &Bound<'py, PyTuple>,
         max_depth: Option<usize>,
+        min_depth: usize,
         follow_symlinks: bool,
         max_filesize: Option<u64>,
+        min_filesize: Option<u64>,
+        #[pyo3(from_py_with = "extract_timestamp")] modified_after: Option<f64>,
+        #[pyo3(from_py_with = "extract_timestamp")] modified_before: Option<f64>,
+        #[pyo3(from_py_with = "extract_timestamp")] accessed_before: Option<f64>,
         global_ignore_files: Option<&Bound<'py, PySequence>>,
         custom_ignore_filenames: Option<&Bound<'py, PySequence>>,
         overrides: Option<&Bound<'py, PyAny>>,
@@ -111,10 +188,21 @@ impl Walk {
         require_git: bool,
         ignore_case_insensitive: bool,
         sort: Option<Bound<'py, PyAny>>,
+        sort_key: Option<PyObject>,
+        reverse: bool,
+        contents_first: bool,
+        order: Order,
         same_file_system: bool,
         skip_stdout: bool,
         filter_entry: Option<PyObject>,
         onerror: Option<PyObject>,
+        on_skip: Option<PyObject>,
+        threads: Option<usize>,
+        path_type: Option<PyObject>,
+        prefetch_metadata: bool,
+        unique_inodes: bool,
+        count_skipped: bool,
+        count_bytes: bool,
     ) -> PyResult<Self> {
         let paths = PyList::new_bound(py, paths);
         let global_ignore_files = match global_ignore_files {
@@ -127,6 +215,7 @@ impl Walk {
         };
         let sort = match sort {
             Some(sort) => {
+                SortMode::from_object(&sort)?;
                 if sort.is_truthy()? {
                     Some(sort)
                 } else {
@@ -135,12 +224,22 @@ impl Walk {
             }
             None => None,
         };
+        if reverse && sort.is_none() && sort_key.is_none() {
+            return Err(PyValueError::new_err("reverse requires sort or sort_key"));
+        }
         let mut instance = Self {
             state: State::Unopened,
             paths: Some(paths.unbind()),
             max_depth,
+            min_depth,
             follow_symlinks,
-            max_filesize,
+            metadata_filter: MetadataFilter {
+                max_filesize,
+                min_filesize,
+                modified_after,
+                modified_before,
+                accessed_before,
+            },
             global_ignore_files,
             custom_ignore_filenames,
             overrides: None,
@@ -154,14 +253,35 @@ impl Walk {
             require_git,
             ignore_case_insensitive,
             sort: sort.map(Bound::unbind),
+            sort_key,
+            reverse,
+            contents_first,
+            order,
             same_file_system,
             skip_stdout,
             filter_entry,
             onerror,
+            on_skip,
+            threads,
+            path_type: None,
+            path_kind: PathKind::Entry,
+            prefetch_metadata,
+            unique_inodes,
+            count_skipped,
+            count_bytes,
+            as_bytes: false,
+            roots: Roots::default(),
+            callback_error: CallbackError::default(),
+            stats: Stats::default(),
+            explainer: None,
+            skip_dir: SkipDir::default(),
+            current_dir: None,
+            peeked: None,
         };
         if let Some(overrides) = overrides {
             instance.set_overrides(py, Some(overrides))?;
         }
+        instance.set_path_type(py, path_type)?;
         Ok(instance)
     }
 
@@ -207,6 +327,18 @@ impl Walk {
         Ok(())
     }
 
+    #[getter]
+    fn min_depth(&self) -> usize {
+        self.min_depth
+    }
+
+    #[setter]
+    fn set_min_depth(&mut self, value: usize) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.min_depth = value;
+        Ok(())
+    }
+
     #[getter]
     fn follow_symlinks(&self) -> bool {
         self.follow_symlinks
@@ -221,13 +353,61 @@ impl Walk {
 
     #[getter]
     fn max_filesize(&self) -> Option<u64> {
-        self.max_filesize
+        self.metadata_filter.max_filesize
     }
 
     #[setter]
     fn set_max_filesize(&mut self, value: Option<u64>) -> PyResult<()> {
         self.check_not_started_setter()?;
-        self.max_filesize = value;
+        self.metadata_filter.max_filesize = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn min_filesize(&self) -> Option<u64> {
+        self.metadata_filter.min_filesize
+    }
+
+    #[setter]
+    fn set_min_filesize(&mut self, value: Option<u64>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.metadata_filter.min_filesize = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn modified_after(&self) -> Option<f64> {
+        self.metadata_filter.modified_after
+    }
+
+    #[setter]
+    fn set_modified_after(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.metadata_filter.modified_after = extract_timestamp(value)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn modified_before(&self) -> Option<f64> {
+        self.metadata_filter.modified_before
+    }
+
+    #[setter]
+    fn set_modified_before(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.metadata_filter.modified_before = extract_timestamp(value)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn accessed_before(&self) -> Option<f64> {
+        self.metadata_filter.accessed_before
+    }
+
+    #[setter]
+    fn set_accessed_before(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.metadata_filter.accessed_before = extract_timestamp(value)?;
         Ok(())
     }
 
@@ -390,6 +570,7 @@ impl Walk {
     #[setter]
     fn set_sort(&mut self, py: Python<'_>, value: PyObject) -> PyResult<()> {
         self.check_not_started_setter()?;
+        SortMode::from_object(value.bind(py))?;
         self.sort = if value.is_truthy(py)? {
             Some(value)
         } else {
@@ -398,6 +579,54 @@ impl Walk {
         Ok(())
     }
 
+    #[getter]
+    fn sort_key(&self) -> Option<&PyObject> {
+        self.sort_key.as_ref()
+    }
+
+    #[setter]
+    fn set_sort_key(&mut self, value: Option<PyObject>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.sort_key = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn reverse(&self) -> bool {
+        self.reverse
+    }
+
+    #[setter]
+    fn set_reverse(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.reverse = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn contents_first(&self) -> bool {
+        self.contents_first
+    }
+
+    #[setter]
+    fn set_contents_first(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.contents_first = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn order(&self) -> &'static str {
+        self.order.as_str()
+    }
+
+    #[setter]
+    fn set_order(&mut self, value: Order) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.order = value;
+        Ok(())
+    }
+
     #[getter]
     fn same_file_system(&self) -> bool {
         self.same_file_system
@@ -446,73 +675,336 @@ impl Walk {
         Ok(())
     }
 
+    #[getter]
+    fn on_skip(&self) -> Option<PyObject> {
+        self.on_skip.clone()
+    }
+
+    #[setter]
+    fn set_on_skip(&mut self, value: Option<PyObject>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.on_skip = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    #[setter]
+    fn set_threads(&mut self, value: Option<usize>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.threads = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn path_type(&self) -> Option<PyObject> {
+        self.path_type.clone()
+    }
+
+    #[setter]
+    fn set_path_type(&mut self, py: Python<'_>, value: Option<PyObject>) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.path_kind = PathKind::new(py, value.as_ref())?;
+        self.path_type = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn prefetch_metadata(&self) -> bool {
+        self.prefetch_metadata
+    }
+
+    #[setter]
+    fn set_prefetch_metadata(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.prefetch_metadata = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn unique_inodes(&self) -> bool {
+        self.unique_inodes
+    }
+
+    #[setter]
+    fn set_unique_inodes(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.unique_inodes = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn count_skipped(&self) -> bool {
+        self.count_skipped
+    }
+
+    #[setter]
+    fn set_count_skipped(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.count_skipped = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn count_bytes(&self) -> bool {
+        self.count_bytes
+    }
+
+    #[setter]
+    fn set_count_bytes(&mut self, value: bool) -> PyResult<()> {
+        self.check_not_started_setter()?;
+        self.count_bytes = value;
+        Ok(())
+    }
+
+    /// Counts of the entries and errors seen so far, as a :class:`WalkStats`
+    #[getter]
+    fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.stats
+            .to_object(py, self.count_skipped, self.count_bytes)
+    }
+
+    /// Explain why ``path`` would be skipped or yielded by this walk
+    fn explain(&self, py: Python<'_>, path: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let roots = fspath_list(self.paths.as_ref().unwrap().bind(py))?;
+        let (root, relative) = split_root(&roots, &path).ok_or_else(|| {
+            PyValueError::new_err("path is not inside any of the paths being walked")
+        })?;
+        let explainer = self.explainer(py)?;
+
+        match py.allow_threads(|| explainer.explain(root, &relative)) {
+            Some(mat) => mat.into_py(py),
+            None => Ok(py.None()),
+        }
+    }
+
     fn __enter__(mut self_: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
-        self_.state = match self_.state {
-            State::Unopened => State::Opened,
-            State::Opened | State::Started(_) => {
-                return Err(PyRuntimeError::new_err(
-                    "Walk context manager is not reentrant",
-                ))
-            }
-            State::Closed => return Err(PyRuntimeError::new_err("Walk is closed")),
-        };
+        self_.open()?;
         Ok(self_)
     }
 
+    fn __aenter__(mut self_: PyRefMut<'_, Self>) -> PyResult<PyObject> {
+        let py = self_.py();
+        let future = create_future(py)?;
+        self_.open()?;
+        future.call_method1("set_result", (self_,))?;
+        Ok(future.unbind())
+    }
+
     /// Close the iterator and free acquired resources
     ///
     /// It is recommended to use a ``with`` statement instead.
-    fn close(&mut self) {
-        self.state = State::Closed;
+    fn close(&mut self, py: Python<'_>) {
+        self.stats.finish();
+        let state = mem::replace(&mut self.state, State::Closed);
+        // The parallel walker joins its threads when dropped, which may need
+        // the GIL to finish calling filter_entry.
+        py.allow_threads(|| drop(state));
     }
 
     fn __exit__(
         &mut self,
+        py: Python<'_>,
         _exc_type: Option<&Bound<'_, PyType>>,
-        _exc_val: Option<&Bound<'_, PyException>>,
+        _exc_val: Option<&Bound<'_, PyBaseException>>,
         _exc_tb: Option<&Bound<'_, PyTraceback>>,
     ) {
-        self.close();
+        self.close(py);
+    }
+
+    fn __aexit__(
+        &mut self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyType>>,
+        _exc_val: Option<&Bound<'_, PyBaseException>>,
+        _exc_tb: Option<&Bound<'_, PyTraceback>>,
+    ) -> PyResult<PyObject> {
+        let future = create_future(py)?;
+        self.close(py);
+        future.call_method1("set_result", (py.None(),))?;
+        Ok(future.unbind())
     }
 
     fn __iter__(self_: PyRef<'_, Self>) -> PyRef<'_, Self> {
         self_
     }
 
-    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<DirEntry>> {
-        self.ensure_started(py)?;
-        let walk = match &mut self.state {
-            State::Started(walk) => walk,
-            _ => unreachable!(),
-        };
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.ensure_started(py, false)?;
+        loop {
+            let walk = match &mut self.state {
+                State::Started(walk) => walk,
+                _ => unreachable!(),
+            };
+            let result = match self.peeked.take() {
+                Some(result) => Some(result),
+                None => py.allow_threads(|| walk.next()),
+            };
+            let finished = result.is_none();
+            if let Some(entry) = self.handle_result(py, result)? {
+                return Ok(Some(entry));
+            }
+            if finished {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Skip the remaining entries of the directory most recently yielded, or
+    /// of its parent directory if a file was most recently yielded.
+    fn skip_current_dir(&mut self) -> PyResult<()> {
+        if let State::Started(Walker::Threaded(_)) = self.state {
+            return Err(PyRuntimeError::new_err(
+                "skip_current_dir is not supported when walking with threads or asynchronously",
+            ));
+        }
+        if self.order == Order::Bfs {
+            return Err(PyRuntimeError::new_err(
+                "skip_current_dir is not supported with order='bfs'",
+            ));
+        }
+        if let Some(dir) = self.current_dir.take() {
+            self.skip_dir.set(dir);
+        }
+        Ok(())
+    }
+
+    /// Return a list of up to ``n`` entries, which is empty once the walk is
+    /// finished.
+    fn next_batch(&mut self, py: Python<'_>, n: usize) -> PyResult<Vec<PyObject>> {
+        if n == 0 {
+            return Err(PyValueError::new_err("n must be greater than 0"));
+        }
+        self.ensure_started(py, false)?;
+        if let Some(err) = self.callback_error.take() {
+            return Err(err);
+        }
 
-        while let Some(dent) = py.allow_threads(|| walk.next()) {
-            if let Some(err) = PyErr::take(py) {
-                // Don't pass user-caused errors through onerror, raise directly
-                return Err(err);
+        let mut entries = Vec::with_capacity(n);
+        loop {
+            if let Some(result) = self.peeked.take() {
+                if let Some(entry) = self.handle_result(py, Some(result))? {
+                    entries.push(entry);
+                }
+            }
+            let remaining = n - entries.len();
+            if remaining == 0 {
+                break;
             }
 
-            match dent {
-                Ok(dent) => {
-                    if let Some(err) = dent.error() {
-                        self.convert_and_call_onerror(py, err.clone())?;
+            let walk = match &mut self.state {
+                State::Started(walk) => walk,
+                _ => unreachable!(),
+            };
+            let callback_error = &self.callback_error;
+            let min_depth = self.min_depth;
+            let (explainer, max_depth) = (&self.explainer, self.max_depth);
+            let mut dents = Vec::with_capacity(remaining);
+            let mut skipped = Vec::new();
+            // Pull entries without the GIL until the batch is full or there's
+            // an error, which must be handled before any later entries.
+            let stop = py.allow_threads(|| {
+                while dents.len() < remaining {
+                    let result = match walk.next() {
+                        Some(result) => result,
+                        None => return BatchStop::Finished,
+                    };
+                    if callback_error.is_set() {
+                        // Discarded, like in __next__
+                        return BatchStop::CallbackError;
+                    }
+                    match result {
+                        Ok(dent) if dent.error().is_some() => return BatchStop::Error(Ok(dent)),
+                        Ok(dent) => {
+                            if let Some(explainer) = explainer {
+                                skipped.extend(explainer.skipped(&dent, max_depth));
+                            }
+                            if dent.depth() >= min_depth {
+                                dents.push(dent);
+                            }
+                        }
+                        Err(err) => return BatchStop::Error(Err(err)),
                     }
-                    return Ok(Some(DirEntry::new(dent, self.follow_symlinks)));
                 }
-                Err(err) => {
-                    if let Some(onerror) = self.onerror.clone() {
-                        convert_and_call_onerror(py, onerror.bind(py), err)?;
+                BatchStop::Full
+            });
+
+            self.call_on_skip(py, skipped)?;
+            if let Some(dent) = dents.last() {
+                self.current_dir = current_dir(dent);
+            }
+            for dent in dents {
+                self.stats.add_entry(&dent);
+                entries.push(self.convert_entry(py, dent)?);
+            }
+            match stop {
+                BatchStop::Error(result) => {
+                    self.peeked = Some(result);
+                    if !entries.is_empty() {
+                        break;
                     }
                 }
+                BatchStop::CallbackError => {
+                    // Raised by the next call if there are entries to return
+                    if entries.is_empty() {
+                        return Err(self.callback_error.take().unwrap());
+                    }
+                    break;
+                }
+                BatchStop::Finished => {
+                    self.stats.finish();
+                    break;
+                }
+                BatchStop::Full => break,
             }
         }
+        Ok(entries)
+    }
 
-        if let Some(err) = PyErr::take(py) {
-            // Don't pass user-caused errors through onerror, raise directly
-            return Err(err);
+    /// Return an iterator of lists of up to ``size`` entries.
+    fn batches(self_: PyRef<'_, Self>, size: usize) -> PyResult<Batches> {
+        if size == 0 {
+            return Err(PyValueError::new_err("size must be greater than 0"));
         }
+        Ok(Batches::new(self_.into(), size))
+    }
+
+    fn __aiter__(self_: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        self_
+    }
+
+    /// Return an :class:`asyncio.Future` for the next entry, which is produced
+    /// on a background thread.
+    fn __anext__(mut self_: PyRefMut<'_, Self>) -> PyResult<PyObject> {
+        let py = self_.py();
+        let future = create_future(py)?;
+        self_.ensure_started(py, true)?;
+        if matches!(self_.state, State::Started(Walker::Sequential(_))) {
+            return Err(PyRuntimeError::new_err(
+                "Walk was started by synchronous iteration",
+            ));
+        }
+        let walk: Py<Walk> = self_.into();
+
+        // Stop walking if the task awaiting the entry is cancelled
+        let on_done = {
+            let walk = walk.clone_ref(py);
+            PyCFunction::new_closure_bound(py, None, None, move |args, _kwargs| {
+                let py = args.py();
+                if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+                    walk.bind(py).try_borrow_mut()?.close(py);
+                }
+                PyResult::Ok(())
+            })?
+        };
+        future.call_method1("add_done_callback", (on_done,))?;
 
-        Ok(None)
+        Walk::poll(py, walk, future.clone().unbind())?;
+        Ok(future.unbind())
     }
 
     fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
@@ -534,12 +1026,21 @@ impl Walk {
         if let Some(sort) = &self.sort {
             visit.call(sort)?;
         }
+        if let Some(sort_key) = &self.sort_key {
+            visit.call(sort_key)?;
+        }
         if let Some(filter_entry) = &self.filter_entry {
             visit.call(filter_entry)?;
         }
         if let Some(onerror) = &self.onerror {
             visit.call(onerror)?;
         }
+        if let Some(on_skip) = &self.on_skip {
+            visit.call(on_skip)?;
+        }
+        if let Some(path_type) = &self.path_type {
+            visit.call(path_type)?;
+        }
         Ok(())
     }
 
@@ -550,8 +1051,12 @@ impl Walk {
         self.overrides = None;
         self.types = None;
         self.sort = None;
+        self.sort_key = None;
         self.filter_entry = None;
         self.onerror = None;
+        self.on_skip = None;
+        self.path_type = None;
+        self.path_kind = PathKind::Entry;
     }
 }
 
@@ -565,12 +1070,17 @@ impl Walk {
         Ok(())
     }
 
-    fn build(&mut self, py: Python<'_>) -> PyResult<ignore::Walk> {
+    fn build(&mut self, py: Python<'_>, background: bool) -> PyResult<Walker> {
         let paths = self.paths.as_ref().unwrap().bind(py);
         if paths.is_empty() {
             return Err(PyTypeError::new_err("Must specify at least one path"));
         }
-        let paths = fspath_list(paths)?;
+        let (paths, as_bytes) = fspath_list_bytes(paths)?;
+        self.as_bytes = as_bytes;
+        self.roots = Roots::new(paths.iter().map(PathBuf::from).collect());
+        if self.on_skip.is_some() || self.count_skipped {
+            self.explainer = Some(self.explainer(py)?);
+        }
 
         // The ignore crate treats "-" specially and just returns "<stdin>" if you try to walk it.
         let stdin = Path::new("-");
@@ -593,7 +1103,6 @@ impl Walk {
         builder
             .max_depth(self.max_depth)
             .follow_links(self.follow_symlinks)
-            .max_filesize(self.max_filesize)
             .hidden(self.hidden)
             .parents(self.parents)
             .ignore(self.ignore)
@@ -621,116 +1130,233 @@ impl Walk {
             builder.add_custom_ignore_filename(path.extract::<OsString>()?);
         }
 
-        if let Some(filter_entry) = self.filter_entry.clone() {
-            let follow_symlinks = self.follow_symlinks;
-            builder.filter_entry(move |dent| {
-                let py_dent = DirEntry::new(dent.clone(), follow_symlinks);
-                Python::with_gil(|py| {
-                    filter_entry
-                        .call1(py, (py_dent,))
-                        .and_then(|result| result.is_truthy(py))
-                        .unwrap_or_else(|err| {
-                            if !PyErr::occurred(py) {
-                                err.restore(py);
-                            }
-
-                            // Return true so that we reach the __next__ method where we can return
-                            // the error can be raised
-                            true
-                        })
-                })
-            });
+        // skip_current_dir is only supported when entries are produced on
+        // demand, so it's only checked by the sequential walker.
+        let skip_dir = (self.threads.is_none() && !background && self.order == Order::Dfs)
+            .then(|| self.skip_dir.clone());
+        // max_filesize is checked here rather than by the walker, which
+        // otherwise skips filter_entry for files.
+        let filter =
+            (self.filter_entry.is_some() || skip_dir.is_some() || !self.metadata_filter.is_empty())
+                .then(|| EntryFilter {
+                    skip_dir,
+                    metadata_filter: self.metadata_filter,
+                    filter_entry: self.filter_entry.clone(),
+                    roots: self.roots.clone(),
+                    follow_symlinks: self.follow_symlinks,
+                    as_bytes: self.as_bytes,
+                    callback_error: self.callback_error.clone(),
+                });
+        if let Some(filter) = filter.clone() {
+            builder.filter_entry(move |dent| filter.accepts(&Dent::Walk(dent.clone())));
         }
 
-        if let Some(sort) = self.sort.clone() {
-            if sort.bind(py).is_callable() {
-                builder.sort_by_file_path(move |a, b| {
-                    fn inner(sort_key: &PyObject, a: &Path, b: &Path) -> PyResult<Ordering> {
-                        Python::with_gil(|py| {
-                            let a = sort_key.call1(py, (a.as_os_str(),))?;
-                            let b = sort_key.call1(py, (b.as_os_str(),))?;
-
-                            let ra = a.bind(py);
-                            let rb = b.bind(py);
-
-                            Ok(match ra.gt(rb)? as i8 - ra.lt(rb)? as i8 {
-                                -1 => Ordering::Less,
-                                0 => Ordering::Equal,
-                                1 => Ordering::Greater,
-                                _ => unreachable!(),
-                            })
-                        })
-                    }
-
-                    inner(&sort, a, b).unwrap_or_else(|err| {
-                        Python::with_gil(|py| {
-                            if !PyErr::occurred(py) {
-                                err.restore(py);
-                            }
-                        });
-                        a.cmp(b)
-                    })
-                });
+        let compare: Option<Comparator> = match (self.sort.clone(), self.sort_key.clone()) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err("sort and sort_key can't both be set"));
+            }
+            (Some(sort), None) => Some(match SortMode::from_object(sort.bind(py))? {
+                Some(mode) => mode.comparator(self.follow_symlinks),
+                None => {
+                    let comparator = KeyComparator::new(
+                        sort,
+                        KeyArg::Path,
+                        self.as_bytes,
+                        self.callback_error.clone(),
+                    );
+                    Box::new(move |a, b| comparator.compare(a, b))
+                }
+            }),
+            (None, Some(sort_key)) => {
+                let comparator = KeyComparator::new(
+                    sort_key,
+                    KeyArg::Entry {
+                        roots: self.roots.clone(),
+                        follow_symlinks: self.follow_symlinks,
+                    },
+                    self.as_bytes,
+                    self.callback_error.clone(),
+                );
+                Some(Box::new(move |a, b| comparator.compare(a, b)))
+            }
+            (None, None) if self.reverse => {
+                return Err(PyValueError::new_err("reverse requires sort or sort_key"));
+            }
+            (None, None) => None,
+        };
+        let compare = compare.map(|compare| -> Comparator {
+            if self.reverse {
+                Box::new(move |a, b| compare(a, b).reverse())
             } else {
-                builder.sort_by_file_path(|a, b| a.cmp(b));
+                compare
             }
+        });
+        if compare.is_some() && self.threads.is_some() {
+            return Err(PyValueError::new_err(
+                "sort is not supported when walking with threads",
+            ));
         }
 
-        if let Some(overrides) = &self.overrides {
-            let overrides = overrides.bind(py);
-            let path: OsString = fspath(&overrides.getattr("path")?)?.extract()?;
-            let mut overrides_builder = OverrideBuilder::new(path);
-            for override_ in overrides.iter()? {
-                let override_ = override_?;
-                let x = override_.get_item(0)?;
-                let glob = &*x.extract::<PyBackedStr>()?;
-                let case_insensitive = override_.get_item(1)?.extract()?;
-                overrides_builder
-                    .case_insensitive(case_insensitive)
-                    .map_err(|err| err.into_py_err(py))?;
-                overrides_builder
-                    .add(glob)
-                    .map_err(|err| err.into_py_err(py))?;
+        let seen_inodes = self.unique_inodes.then(SeenInodes::default);
+        let prefetch = Prefetch::new(self.prefetch_metadata, self.count_bytes);
+        if self.order == Order::Bfs {
+            if self.threads.is_some() {
+                return Err(PyValueError::new_err(
+                    "order='bfs' is not supported when walking with threads",
+                ));
             }
-            let overrides = overrides_builder
-                .build()
-                .map_err(|err| err.into_py_err(py))?;
-            builder.overrides(overrides);
+            if self.contents_first {
+                return Err(PyValueError::new_err(
+                    "contents_first is not supported with order='bfs'",
+                ));
+            }
+            let matcher = Explainer {
+                details: false,
+                ..self.explainer(py)?
+            };
+            let walk = BreadthFirst::new(
+                matcher,
+                paths.iter().map(PathBuf::from).collect(),
+                self.max_depth,
+                self.follow_symlinks,
+                self.same_file_system,
+                self.skip_stdout,
+                filter,
+                compare,
+            );
+            let walk = walk.map(move |result| Entry::from_result(result, prefetch));
+            return Ok(self.sequential(Box::new(walk), seen_inodes, background));
+        }
+
+        if let Some(compare) = compare {
+            builder.sort_by_file_path(compare);
+        }
+
+        if let Some(overrides) = &self.overrides {
+            builder.overrides(build_overrides(overrides.bind(py))?);
         }
 
         if let Some(types) = &self.types {
-            let types = types.borrow(py);
-            let mut types_builder = TypesBuilder::new();
-            for name in types.__iter__(py)?.bind(py) {
-                let name = name?;
-                let globs: Py<PyTuple> = types.__getitem__(py, &name)?.extract()?;
-                for glob in globs.extract::<Vec<PyBackedStr>>(py)? {
-                    types_builder
-                        .add(&*name.extract::<PyBackedStr>()?, &glob)
-                        .map_err(|err| err.into_py_err(py))?;
-                }
-            }
-            for selection in &types.selections {
-                match selection {
-                    Selection::Select(name) => {
-                        types_builder.select(name);
-                    }
-                    Selection::Negate(name) => {
-                        types_builder.negate(name);
-                    }
-                }
+            builder.types(types.borrow(py).build(py)?);
+        }
+
+        if let Some(threads) = self.threads {
+            if self.contents_first {
+                return Err(PyValueError::new_err(
+                    "contents_first is not supported when walking with threads",
+                ));
             }
-            let types = types_builder.build().map_err(|err| err.into_py_err(py))?;
-            builder.types(types);
+            builder.threads(threads);
+            return Ok(Walker::Threaded(ThreadedWalker::parallel(
+                builder.build_parallel(),
+                prefetch,
+                seen_inodes,
+            )));
+        }
+
+        let walk: Box<dyn Iterator<Item = IgnoreResult> + Send> = if self.contents_first {
+            Box::new(ContentsFirst::new(builder.build()))
+        } else {
+            Box::new(builder.build())
+        };
+        let walk = walk.map(move |result| Entry::from_result(result.map(Dent::Walk), prefetch));
+        Ok(self.sequential(Box::new(walk), seen_inodes, background))
+    }
+
+    /// Wrap a sequential walker, skipping repeated inodes if `seen_inodes` is
+    /// given and running it on a background thread if `background` is true.
+    fn sequential(
+        &self,
+        walk: SequentialWalker,
+        seen_inodes: Option<SeenInodes>,
+        background: bool,
+    ) -> Walker {
+        let walk: SequentialWalker = match seen_inodes {
+            Some(seen_inodes) => Box::new(walk.filter(move |result| seen_inodes.is_first(result))),
+            None => walk,
+        };
+        if background {
+            Walker::Threaded(ThreadedWalker::sequential(walk))
+        } else {
+            Walker::Sequential(walk)
         }
+    }
+
+    /// Create an `Explainer` which mirrors the filtering done by this walk.
+    fn explainer(&self, py: Python<'_>) -> PyResult<Explainer> {
+        let global_ignores = fspath_list(self.global_ignore_files.as_ref().unwrap().bind(py))?
+            .into_iter()
+            .map(|path| {
+                let mut builder = GitignoreBuilder::new("");
+                builder.add(path);
+                builder.build().unwrap_or_else(|_| Gitignore::empty())
+            })
+            .collect();
+        let git_global_matcher = if self.git_global {
+            let mut builder = GitignoreBuilder::new("");
+            builder
+                .case_insensitive(self.ignore_case_insensitive)
+                .unwrap();
+            builder.build_global().0
+        } else {
+            Gitignore::empty()
+        };
+        Ok(Explainer {
+            overrides: self
+                .overrides
+                .as_ref()
+                .map(|overrides| OverrideGlobs::new(overrides.bind(py)))
+                .transpose()?,
+            types: self
+                .types
+                .as_ref()
+                .map(|types| types.borrow(py).build(py))
+                .transpose()?,
+            custom_ignore_filenames: self
+                .custom_ignore_filenames
+                .as_ref()
+                .unwrap()
+                .bind(py)
+                .extract()?,
+            global_ignores,
+            git_global_matcher,
+            follow_symlinks: self.follow_symlinks,
+            same_file_system: self.same_file_system,
+            metadata_filter: self.metadata_filter,
+            hidden: self.hidden,
+            parents: self.parents,
+            ignore: self.ignore,
+            git_global: self.git_global,
+            git_ignore: self.git_ignore,
+            git_exclude: self.git_exclude,
+            require_git: self.require_git,
+            ignore_case_insensitive: self.ignore_case_insensitive,
+            details: true,
+            dirs: DirCache::default(),
+            lines: LineCache::default(),
+        })
+    }
 
-        Ok(builder.build())
+    fn open(&mut self) -> PyResult<()> {
+        self.state = match self.state {
+            State::Unopened => State::Opened,
+            State::Opened | State::Started(_) => {
+                return Err(PyRuntimeError::new_err(
+                    "Walk context manager is not reentrant",
+                ))
+            }
+            State::Closed => return Err(PyRuntimeError::new_err("Walk is closed")),
+        };
+        Ok(())
     }
 
-    fn ensure_started(&mut self, py: Python<'_>) -> PyResult<()> {
+    /// Start walking if necessary. If `background` is true the entries are
+    /// produced on a background thread, so that they can be polled.
+    fn ensure_started(&mut self, py: Python<'_>, background: bool) -> PyResult<()> {
         match &self.state {
             State::Unopened | State::Opened => {
-                self.state = State::Started(Box::new(self.build(py)?));
+                self.stats.start();
+                self.state = State::Started(self.build(py, background)?);
             }
             State::Closed => return Err(PyRuntimeError::new_err("Walk is closed")),
             State::Started(_) => (),
@@ -739,6 +1365,198 @@ impl Walk {
         Ok(())
     }
 
+    /// Convert a result from the walker, returning `None` if there was an error
+    /// which `onerror` handled or the walk is finished.
+    fn handle_result(
+        &mut self,
+        py: Python<'_>,
+        result: Option<WalkResult>,
+    ) -> PyResult<Option<PyObject>> {
+        match self.accept_result(py, result)? {
+            Some(entry) => Ok(Some(self.convert_entry(py, entry)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Handle a result from the walker like `handle_result`, returning the
+    /// entry to yield without converting it.
+    fn accept_result(
+        &mut self,
+        py: Python<'_>,
+        result: Option<WalkResult>,
+    ) -> PyResult<Option<Entry>> {
+        if let Some(err) = self.callback_error.take() {
+            // Don't pass user-caused errors through onerror, raise directly
+            return Err(err);
+        }
+
+        match result {
+            // Pulled before the directory was skipped
+            Some(Ok(dent)) if self.skip_dir.contains(dent.path()) => Ok(None),
+            Some(Ok(dent)) => {
+                if let Some(err) = dent.error() {
+                    self.stats.add_error();
+                    self.convert_and_call_onerror(py, err.clone())?;
+                }
+                let skipped = self.skipped(py, &dent);
+                self.call_on_skip(py, skipped)?;
+                if dent.depth() < self.min_depth {
+                    return Ok(None);
+                }
+                self.current_dir = current_dir(&dent);
+                self.stats.add_entry(&dent);
+                Ok(Some(dent))
+            }
+            Some(Err(err)) => {
+                self.stats.add_error();
+                self.convert_and_call_onerror(py, err)?;
+                Ok(None)
+            }
+            None => {
+                self.stats.finish();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Call `f` with each entry the walk yields, without converting them to
+    /// Python objects. Errors and callbacks are handled like `__next__`.
+    fn for_each_entry(&mut self, py: Python<'_>, mut f: impl FnMut(&Entry) + Send) -> PyResult<()> {
+        self.ensure_started(py, false)?;
+        loop {
+            let walk = match &mut self.state {
+                State::Started(walk) => walk,
+                _ => unreachable!(),
+            };
+            let (callback_error, stats) = (&self.callback_error, &mut self.stats);
+            let fast_path = self.explainer.is_none();
+            let min_depth = self.min_depth;
+            // Pull entries without the GIL until there's a result which needs
+            // it to be handled.
+            let result = py.allow_threads(|| loop {
+                match walk.next() {
+                    Some(Ok(entry))
+                        if fast_path
+                            && entry.error().is_none()
+                            && entry.depth() >= min_depth
+                            && !callback_error.is_set() =>
+                    {
+                        stats.add_entry(&entry);
+                        f(&entry);
+                    }
+                    result => return result,
+                }
+            });
+            let finished = result.is_none();
+            if let Some(entry) = self.accept_result(py, result)? {
+                f(&entry);
+            }
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Find the entries which the walker skips after yielding `dent`, if
+    /// `on_skip` is set.
+    fn skipped(&self, py: Python<'_>, dent: &Dent) -> Vec<(PathBuf, MatchInfo)> {
+        match &self.explainer {
+            Some(explainer) => {
+                let max_depth = self.max_depth;
+                py.allow_threads(|| explainer.skipped(dent, max_depth))
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn call_on_skip(&mut self, py: Python<'_>, skipped: Vec<(PathBuf, MatchInfo)>) -> PyResult<()> {
+        for (path, mat) in skipped {
+            self.stats.add_skipped(&mat);
+            if let Some(on_skip) = &self.on_skip {
+                let path = path_to_object(py, &path, self.as_bytes)?;
+                on_skip.call1(py, (path, mat.into_py(py)?))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve `future` with the next entry if one is available, otherwise
+    /// try again once the walker thread produces one.
+    fn poll(py: Python<'_>, walk: Py<Walk>, future: PyObject) -> PyResult<()> {
+        let future = future.bind(py);
+        loop {
+            if future.call_method0("done")?.is_truthy()? {
+                // The awaiting task was cancelled
+                return Ok(());
+            }
+            let mut this = walk.bind(py).try_borrow_mut()?;
+            let peeked = this.peeked.take();
+            let poll = match &this.state {
+                _ if peeked.is_some() => Poll::Ready(peeked),
+                State::Started(Walker::Threaded(walker)) => {
+                    let callback = {
+                        let walk = walk.clone_ref(py);
+                        let future = future.clone().unbind();
+                        PyCFunction::new_closure_bound(py, None, None, move |args, _kwargs| {
+                            let py = args.py();
+                            Walk::poll(py, walk.clone_ref(py), future.clone_ref(py))
+                        })?
+                    };
+                    let call_soon_threadsafe = future
+                        .call_method0("get_loop")?
+                        .getattr("call_soon_threadsafe")?;
+                    let args = PyTuple::new_bound(py, [callback]);
+                    walker.poll_next(Waker::new(call_soon_threadsafe.unbind(), args.unbind()))
+                }
+                // Closed while waiting for the entry, or already finished
+                _ => Poll::Ready(None),
+            };
+            let result = match poll {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Ok(()),
+            };
+            let finished = result.is_none();
+            match this.handle_result(py, result) {
+                Ok(Some(entry)) => {
+                    future.call_method1("set_result", (entry,))?;
+                    return Ok(());
+                }
+                Ok(None) if finished => {
+                    let err = PyStopAsyncIteration::new_err(());
+                    future.call_method1("set_exception", (err.into_value(py),))?;
+                    return Ok(());
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    future.call_method1("set_exception", (err.into_value(py),))?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Convert an entry to the object yielded by the walk.
+    fn convert_entry(&self, py: Python<'_>, entry: Entry) -> PyResult<PyObject> {
+        let dent = &entry.dent;
+        Ok(match &self.path_kind {
+            PathKind::Entry => {
+                let entry = DirEntry::new(
+                    entry.dent,
+                    self.roots.clone(),
+                    self.follow_symlinks,
+                    self.as_bytes,
+                    entry.metadata.map(|metadata| *metadata),
+                );
+                Py::new(py, entry)?.into_py(py)
+            }
+            PathKind::Str => dent.path().to_object(py),
+            PathKind::Bytes => path_to_bytes(py, dent.path())?.into_py(py),
+            PathKind::Factory(path_type) => {
+                path_type.call1(py, (path_to_object(py, dent.path(), self.as_bytes)?,))?
+            }
+        })
+    }
+
     fn convert_and_call_onerror(&self, py: Python<'_>, err: ignore::Error) -> PyResult<()> {
         if let Some(onerror) = self.onerror.clone() {
             convert_and_call_onerror(py, onerror.bind(py), err)?;
@@ -751,6 +1569,9 @@ impl Drop for Walk {
     fn drop(&mut self) {
         if matches!(self.state, State::Started(_)) {
             Python::with_gil(|py| {
+                let state = mem::replace(&mut self.state, State::Closed);
+                py.allow_threads(|| drop(state));
+
                 // SAFETY: We're borrowing PyExc_ResourceWarning so need to incref
                 let resource_warning_type =
                     unsafe { Bound::from_borrowed_ptr(py, ffi::PyExc_ResourceWarning) };
@@ -775,6 +1596,88 @@ impl Drop for Walk {
     }
 }
 
+/// The directory which `Walk.skip_current_dir` skips after yielding `dent`.
+fn current_dir(dent: &Dent) -> Option<PathBuf> {
+    if dent.file_type().map_or(false, |ft| ft.is_dir()) {
+        Some(dent.path().to_path_buf())
+    } else if dent.depth() > 0 {
+        dent.path().parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+/// What `Walk` yields for each entry, depending on `path_type`.
+enum PathKind {
+    Entry,
+    Str,
+    Bytes,
+    /// A callable such as `pathlib.Path`, which is called with the path as a
+    /// `str`, or `bytes` if the walked paths are bytes.
+    Factory(PyObject),
+}
+
+impl PathKind {
+    fn new(py: Python<'_>, path_type: Option<&PyObject>) -> PyResult<Self> {
+        match path_type {
+            None => Ok(PathKind::Entry),
+            Some(path_type) if path_type.is(&PyString::type_object_bound(py)) => Ok(PathKind::Str),
+            Some(path_type) if path_type.is(&PyBytes::type_object_bound(py)) => Ok(PathKind::Bytes),
+            Some(path_type) if path_type.bind(py).is_callable() => {
+                Ok(PathKind::Factory(path_type.clone_ref(py)))
+            }
+            Some(_) => Err(PyTypeError::new_err("path_type must be callable or None")),
+        }
+    }
+}
+
+/// The order in which entries are yielded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Order {
+    /// Depth first, which is the order of `ignore::Walk`.
+    Dfs,
+    /// Breadth first, yielding every entry at one depth before the next.
+    Bfs,
+}
+
+impl Order {
+    fn as_str(self) -> &'static str {
+        match self {
+            Order::Dfs => "dfs",
+            Order::Bfs => "bfs",
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for Order {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match &*ob.extract::<PyBackedStr>()? {
+            "dfs" => Ok(Order::Dfs),
+            "bfs" => Ok(Order::Bfs),
+            _ => Err(PyValueError::new_err("order must be 'dfs' or 'bfs'")),
+        }
+    }
+}
+
+/// Why `Walk::next_batch` stopped pulling entries from the walker.
+enum BatchStop {
+    Full,
+    Finished,
+    Error(WalkResult),
+    CallbackError,
+}
+
+/// Create a future on the running event loop.
+fn create_future(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    // asyncio is only imported when needed since it's slow to import
+    let get_running_loop = GET_RUNNING_LOOP.get_or_try_init(py, || {
+        let asyncio = py.import_bound("asyncio")?;
+        PyResult::Ok(asyncio.getattr("get_running_loop")?.unbind())
+    })?;
+    let event_loop = get_running_loop.bind(py).call0()?;
+    event_loop.call_method0("create_future")
+}
+
 fn convert_and_call_onerror(
     py: Python<'_>,
     onerror: &Bound<'_, PyAny>,
@@ -794,6 +1697,10 @@ fn _lib(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     mutable_mapping_type.call_method1("register", (Types::type_object_bound(py),))?;
     m.add_class::<Types>()?;
     m.add_class::<DirEntry>()?;
+    m.add_class::<Metadata>()?;
+    m.add_class::<gitignore::Gitignore>()?;
+    m.add_class::<CompiledOverrides>()?;
+    m.add_function(wrap_pyfunction!(disk_usage::disk_usage, m)?)?;
 
     let name: Py<PyString> = "_types".into_py(py);
     let globals = m.dict().as_ptr();
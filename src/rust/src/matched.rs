@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use globset::GlobBuilder;
+use ignore::{gitignore, types};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::TYPES_MODULE;
+
+/// Information about the rule which decided whether a path is ignored or
+/// whitelisted, converted to a `Match` from `_types.py`.
+#[derive(Clone, Debug)]
+pub(crate) struct MatchInfo {
+    pub kind: &'static str,
+    pub whitelist: bool,
+    pub glob: Option<String>,
+    pub path: Option<PathBuf>,
+    pub line: Option<u64>,
+    pub file_type: Option<String>,
+}
+
+impl MatchInfo {
+    pub fn new(kind: &'static str, whitelist: bool) -> Self {
+        Self {
+            kind,
+            whitelist,
+            glob: None,
+            path: None,
+            line: None,
+            file_type: None,
+        }
+    }
+
+    /// Convert a gitignore match, returning `None` if nothing matched.
+    pub fn from_gitignore(
+        kind: &'static str,
+        mat: ignore::Match<&gitignore::Glob>,
+    ) -> Option<Self> {
+        Self::from_gitignore_with(kind, mat, find_line)
+    }
+
+    pub fn from_gitignore_with(
+        kind: &'static str,
+        mat: ignore::Match<&gitignore::Glob>,
+        find_line: impl FnOnce(&Path, &str) -> Option<u64>,
+    ) -> Option<Self> {
+        let whitelist = mat.is_whitelist();
+        let glob = mat.inner()?;
+        let line = glob
+            .from()
+            .and_then(|path| find_line(path, glob.original()));
+        Some(Self {
+            glob: Some(glob.original().to_owned()),
+            path: glob.from().map(Path::to_path_buf),
+            line,
+            ..Self::new(kind, whitelist)
+        })
+    }
+
+    /// Convert a file type match, returning `None` if nothing matched.
+    pub fn from_types(mat: ignore::Match<types::Glob<'_>>, path: &Path) -> Option<Self> {
+        let whitelist = mat.is_whitelist();
+        let def = match mat.inner()?.file_type_def() {
+            Some(def) => def,
+            // Nothing matched but a file type was selected
+            None => return Some(Self::new("types", whitelist)),
+        };
+        // The ignore crate doesn't tell us which glob matched, so find the
+        // last one which does, like its glob set does.
+        let glob = path.file_name().and_then(|name| {
+            def.globs()
+                .iter()
+                .rev()
+                .find(|glob| {
+                    GlobBuilder::new(glob)
+                        .literal_separator(true)
+                        .build()
+                        .map_or(false, |glob| glob.compile_matcher().is_match(name))
+                })
+                .cloned()
+        });
+        Some(Self {
+            glob,
+            file_type: Some(def.name().to_owned()),
+            ..Self::new("types", whitelist)
+        })
+    }
+
+    pub fn into_py(self, py: Python<'_>) -> PyResult<PyObject> {
+        let match_type = TYPES_MODULE.get(py).unwrap().bind(py).getattr("Match")?;
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("kind", self.kind)?;
+        kwargs.set_item("whitelist", self.whitelist)?;
+        kwargs.set_item("glob", self.glob)?;
+        kwargs.set_item("path", self.path)?;
+        kwargs.set_item("line", self.line)?;
+        kwargs.set_item("file_type", self.file_type)?;
+        Ok(match_type.call((), Some(&kwargs))?.unbind())
+    }
+}
+
+/// The line numbers of the globs in each ignore file which has matched, so
+/// that a file is only read once however many paths it matches.
+#[derive(Debug, Default)]
+pub(crate) struct LineCache(Mutex<HashMap<PathBuf, Option<HashMap<String, u64>>>>);
+
+impl LineCache {
+    /// Like `MatchInfo::from_gitignore`, using the cached line numbers.
+    pub fn gitignore_match(
+        &self,
+        kind: &'static str,
+        mat: ignore::Match<&gitignore::Glob>,
+    ) -> Option<MatchInfo> {
+        MatchInfo::from_gitignore_with(kind, mat, |path, original| {
+            let mut files = self.0.lock().unwrap();
+            let lines = files
+                .entry(path.to_path_buf())
+                .or_insert_with(|| read_lines(path));
+            lines.as_ref()?.get(original).copied()
+        })
+    }
+}
+
+/// Find the line number of `original` in the ignore file at `path`.
+///
+/// The ignore crate doesn't keep track of line numbers, so we look for the
+/// last line which normalises to the same glob, since later globs take
+/// precedence.
+fn find_line(path: &Path, original: &str) -> Option<u64> {
+    let file = File::open(path).ok()?;
+    let mut line_number = None;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        if normalize_line(&line.ok()?) == original {
+            line_number = Some(i as u64 + 1);
+        }
+    }
+    line_number
+}
+
+/// Map each line of the ignore file at `path` to the number of the last line
+/// which normalises to it, like `find_line`.
+fn read_lines(path: &Path) -> Option<HashMap<String, u64>> {
+    let file = File::open(path).ok()?;
+    let mut lines = HashMap::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        lines.insert(normalize_line(&line.ok()?).to_owned(), i as u64 + 1);
+    }
+    Some(lines)
+}
+
+fn normalize_line(line: &str) -> &str {
+    if line.ends_with("\\ ") {
+        line
+    } else {
+        line.trim_end()
+    }
+}
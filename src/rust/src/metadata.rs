@@ -0,0 +1,58 @@
+use std::fs;
+use std::time::UNIX_EPOCH;
+
+use pyo3::prelude::*;
+
+use crate::util::unix_metadata;
+
+/// The metadata of a `DirEntry`, read without calling `os.stat`.
+#[pyclass(module = "crabwalk", frozen)]
+pub(crate) struct Metadata {
+    #[pyo3(get)]
+    size: u64,
+    #[pyo3(get)]
+    mtime_ns: i128,
+    #[pyo3(get)]
+    mode: u32,
+    #[pyo3(get)]
+    uid: u32,
+    #[pyo3(get)]
+    gid: u32,
+    #[pyo3(get)]
+    nlink: u64,
+    #[pyo3(get)]
+    dev: u64,
+}
+
+impl Metadata {
+    pub(crate) fn new(metadata: &fs::Metadata) -> Self {
+        let mtime_ns = match metadata.modified() {
+            Ok(time) => match time.duration_since(UNIX_EPOCH) {
+                Ok(duration) => duration.as_nanos() as i128,
+                Err(err) => -(err.duration().as_nanos() as i128),
+            },
+            Err(_) => 0,
+        };
+        // Only the size and modification time are available elsewhere
+        let unix = unix_metadata(metadata);
+        Self {
+            size: metadata.len(),
+            mtime_ns,
+            mode: unix.map_or(0, |md| md.mode),
+            uid: unix.map_or(0, |md| md.uid),
+            gid: unix.map_or(0, |md| md.gid),
+            nlink: unix.map_or(0, |md| md.nlink),
+            dev: unix.map_or(0, |md| md.dev),
+        }
+    }
+}
+
+#[pymethods]
+impl Metadata {
+    fn __repr__(&self) -> String {
+        format!(
+            "Metadata(size={}, mtime_ns={}, mode={:#o}, uid={}, gid={}, nlink={}, dev={})",
+            self.size, self.mtime_ns, self.mode, self.uid, self.gid, self.nlink, self.dev
+        )
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs::Metadata;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+
+use crate::walker::Dent;
+
+/// Filters on file metadata. Like `ignore::WalkBuilder::max_filesize`, they
+/// only apply to entries which aren't directories, and entries whose metadata
+/// can't be read are kept.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MetadataFilter {
+    pub max_filesize: Option<u64>,
+    pub min_filesize: Option<u64>,
+    // Timestamps are seconds since the epoch
+    pub modified_after: Option<f64>,
+    pub modified_before: Option<f64>,
+    pub accessed_before: Option<f64>,
+}
+
+impl MetadataFilter {
+    pub fn is_empty(&self) -> bool {
+        self.max_filesize.is_none()
+            && self.min_filesize.is_none()
+            && self.modified_after.is_none()
+            && self.modified_before.is_none()
+            && self.accessed_before.is_none()
+    }
+
+    /// Return the name of the filter which rejects an entry yielded by the
+    /// walker, if any.
+    pub fn rejects_entry(&self, dent: &Dent) -> Option<&'static str> {
+        if self.is_empty() || dent.file_type().map_or(true, |ft| ft.is_dir()) {
+            return None;
+        }
+        self.rejects(&dent.metadata().ok()?)
+    }
+
+    /// Return the name of the filter which rejects a file with this metadata,
+    /// if any.
+    pub fn rejects(&self, metadata: &Metadata) -> Option<&'static str> {
+        if self
+            .max_filesize
+            .map_or(false, |max_filesize| metadata.len() > max_filesize)
+        {
+            return Some("max_filesize");
+        }
+        if self
+            .min_filesize
+            .map_or(false, |min_filesize| metadata.len() < min_filesize)
+        {
+            return Some("min_filesize");
+        }
+        let modified = timestamp(metadata.modified());
+        if let (Some(modified), Some(after)) = (modified, self.modified_after) {
+            if modified <= after {
+                return Some("modified_after");
+            }
+        }
+        if let (Some(modified), Some(before)) = (modified, self.modified_before) {
+            if modified >= before {
+                return Some("modified_before");
+            }
+        }
+        let accessed = timestamp(metadata.accessed());
+        if let (Some(accessed), Some(before)) = (accessed, self.accessed_before) {
+            if accessed >= before {
+                return Some("accessed_before");
+            }
+        }
+        None
+    }
+}
+
+/// Convert a file time to seconds since the epoch, or `None` if the platform
+/// doesn't record it.
+fn timestamp(time: io::Result<SystemTime>) -> Option<f64> {
+    let time = time.ok()?;
+    Some(match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    })
+}
+
+/// Extract a timestamp argument, which may be a `datetime` or a number of
+/// seconds since the epoch.
+pub(crate) fn extract_timestamp(value: &Bound<'_, PyAny>) -> PyResult<Option<f64>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    if let Ok(timestamp) = value.extract::<f64>() {
+        return Ok(Some(timestamp));
+    }
+    match value.getattr("timestamp") {
+        Ok(method) => Ok(Some(method.call0()?.extract()?)),
+        Err(_) => Err(PyTypeError::new_err(format!(
+            "expected a datetime or a number of seconds since the epoch, not {}",
+            value.get_type().name()?
+        ))),
+    }
+}
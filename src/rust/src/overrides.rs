@@ -0,0 +1,128 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedStr;
+
+use crate::error::IntoPyErr;
+use crate::matched::MatchInfo;
+use crate::util::fspath;
+use crate::TYPES_MODULE;
+
+/// Extract the path and `(glob, case_insensitive)` pairs from an `Overrides`
+/// instance from `_types.py`.
+fn extract_overrides(overrides: &Bound<'_, PyAny>) -> PyResult<(OsString, Vec<(String, bool)>)> {
+    let path: OsString = fspath(&overrides.getattr("path")?)?.extract()?;
+    let globs = overrides
+        .iter()?
+        .map(|override_| {
+            let override_ = override_?;
+            let glob = override_.get_item(0)?.extract::<PyBackedStr>()?;
+            let case_insensitive = override_.get_item(1)?.extract()?;
+            Ok((glob.to_string(), case_insensitive))
+        })
+        .collect::<PyResult<_>>()?;
+    Ok((path, globs))
+}
+
+/// Compile an `Overrides` instance from `_types.py`.
+pub(crate) fn build_overrides(overrides: &Bound<'_, PyAny>) -> PyResult<Override> {
+    let py = overrides.py();
+    let (path, globs) = extract_overrides(overrides)?;
+    let mut overrides_builder = OverrideBuilder::new(path);
+    for (glob, case_insensitive) in globs {
+        overrides_builder
+            .case_insensitive(case_insensitive)
+            .map_err(|err| err.into_py_err(py))?;
+        overrides_builder
+            .add(&glob)
+            .map_err(|err| err.into_py_err(py))?;
+    }
+    overrides_builder.build().map_err(|err| err.into_py_err(py))
+}
+
+/// The same matcher as `Override`, which doesn't expose the glob that
+/// matched a path.
+pub(crate) struct OverrideGlobs(Gitignore);
+
+impl OverrideGlobs {
+    /// Compile an `Overrides` instance from `_types.py`.
+    pub fn new(overrides: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let py = overrides.py();
+        let (path, globs) = extract_overrides(overrides)?;
+        let mut builder = GitignoreBuilder::new(path);
+        for (glob, case_insensitive) in globs {
+            builder
+                .case_insensitive(case_insensitive)
+                .map_err(|err| err.into_py_err(py))?;
+            builder
+                .add_line(None, &glob)
+                .map_err(|err| err.into_py_err(py))?;
+        }
+        let gitignore = builder.build().map_err(|err| err.into_py_err(py))?;
+        Ok(Self(gitignore))
+    }
+
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<MatchInfo> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mat = self.0.matched(path, is_dir).invert();
+        // Overrides invert the meaning of the globs, so whitelists are ignores.
+        if mat.is_none() && self.0.num_ignores() > 0 && !is_dir {
+            return Some(MatchInfo::new("override", false));
+        }
+        MatchInfo::from_gitignore("override", mat)
+    }
+}
+
+/// A compiled `Overrides` instance, which can be used without walking a
+/// directory.
+#[pyclass(module = "crabwalk")]
+pub struct CompiledOverrides(OverrideGlobs);
+
+#[pymethods]
+impl CompiledOverrides {
+    #[new]
+    fn new(overrides: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let types_mod = TYPES_MODULE
+            .get(overrides.py())
+            .unwrap()
+            .bind(overrides.py());
+        if !overrides.is_instance(&types_mod.getattr("Overrides")?)? {
+            return Err(PyTypeError::new_err(
+                "overrides must be an Overrides instance",
+            ));
+        }
+        Ok(Self(OverrideGlobs::new(overrides)?))
+    }
+
+    #[getter]
+    fn path(&self) -> &Path {
+        self.0 .0.path()
+    }
+
+    #[getter]
+    fn num_ignores(&self) -> u64 {
+        self.0 .0.num_whitelists()
+    }
+
+    #[getter]
+    fn num_whitelists(&self) -> u64 {
+        self.0 .0.num_ignores()
+    }
+
+    /// Return the override which matched ``path``, or ``None``.
+    #[pyo3(signature = (path, is_dir = false))]
+    fn matched(&self, path: &Bound<'_, PyAny>, is_dir: bool) -> PyResult<PyObject> {
+        let py = path.py();
+        let path: PathBuf = fspath(path)?.extract()?;
+        match self.0.matched(&path, is_dir) {
+            Some(mat) => mat.into_py(py),
+            None => Ok(py.None()),
+        }
+    }
+}
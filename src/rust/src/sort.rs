@@ -0,0 +1,303 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+use crate::direntry::{DirEntry, Roots};
+use crate::util::path_to_object;
+use crate::walker::CallbackError;
+
+/// Compares the paths of two entries in the same directory.
+pub(crate) type Comparator = Box<dyn Fn(&Path, &Path) -> Ordering + Send + Sync>;
+
+/// Compare two sort keys returned by Python callables.
+pub(crate) fn compare_keys(a: &Bound<'_, PyAny>, b: &Bound<'_, PyAny>) -> PyResult<Ordering> {
+    Ok(match a.gt(b)? as i8 - a.lt(b)? as i8 {
+        -1 => Ordering::Less,
+        0 => Ordering::Equal,
+        1 => Ordering::Greater,
+        _ => unreachable!(),
+    })
+}
+
+/// A sort order which is evaluated without calling into Python.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SortMode {
+    Path,
+    Name,
+    NameCi,
+    Natural,
+    Mtime,
+    Size,
+    DirsFirst,
+}
+
+impl SortMode {
+    /// The native sort mode for a value of `Walk.sort`, or `None` if it's a
+    /// key function.
+    pub(crate) fn from_object(sort: &Bound<'_, PyAny>) -> PyResult<Option<Self>> {
+        if let Ok(mode) = sort.downcast::<PyString>() {
+            Self::parse(mode.to_str()?).map(Some)
+        } else if sort.is_callable() {
+            Ok(None)
+        } else {
+            Ok(Some(SortMode::Path))
+        }
+    }
+
+    fn parse(mode: &str) -> PyResult<Self> {
+        match mode {
+            "name" => Ok(SortMode::Name),
+            "name_ci" => Ok(SortMode::NameCi),
+            "natural" => Ok(SortMode::Natural),
+            "mtime" => Ok(SortMode::Mtime),
+            "size" => Ok(SortMode::Size),
+            "dirs_first" => Ok(SortMode::DirsFirst),
+            _ => Err(PyValueError::new_err(format!(
+                "sort must be True, a callable or one of 'name', 'name_ci', 'natural', \
+                 'mtime', 'size' or 'dirs_first', not {mode:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn comparator(self, follow_symlinks: bool) -> Comparator {
+        match self {
+            SortMode::Path => Box::new(|a, b| a.cmp(b)),
+            SortMode::Name => Box::new(|a, b| file_name(a).cmp(file_name(b))),
+            SortMode::NameCi => Box::new(|a, b| compare_names_ci(file_name(a), file_name(b))),
+            SortMode::Natural => Box::new(|a, b| compare_names_natural(file_name(a), file_name(b))),
+            SortMode::Mtime | SortMode::Size | SortMode::DirsFirst => {
+                let cache = Mutex::new(PerDirCache::default());
+                Box::new(move |a, b| {
+                    let mut cache = cache.lock().unwrap();
+                    let ka = cache.get_or_insert(a, |path| MetadataKey::new(path, follow_symlinks));
+                    let kb = cache.get_or_insert(b, |path| MetadataKey::new(path, follow_symlinks));
+                    let ordering = match self {
+                        SortMode::Mtime => ka.mtime.cmp(&kb.mtime),
+                        SortMode::Size => ka.size.cmp(&kb.size),
+                        _ => kb.is_dir.cmp(&ka.is_dir),
+                    };
+                    ordering.then_with(|| file_name(a).cmp(file_name(b)))
+                })
+            }
+        }
+    }
+}
+
+fn file_name(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
+fn compare_names_ci(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a_lossy, b_lossy) = (a.to_string_lossy(), b.to_string_lossy());
+    let a_lower = a_lossy.chars().flat_map(char::to_lowercase);
+    let b_lower = b_lossy.chars().flat_map(char::to_lowercase);
+    a_lower.cmp(b_lower).then_with(|| a.cmp(b))
+}
+
+/// Compare names so that runs of digits are ordered by their numeric value,
+/// e.g. "file2" sorts before "file10".
+fn compare_names_natural(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a_lossy, b_lossy) = (a.to_string_lossy(), b.to_string_lossy());
+    let (mut a_rest, mut b_rest) = (&*a_lossy, &*b_lossy);
+    loop {
+        let (a_chunk, a_tail) = split_chunk(a_rest);
+        let (b_chunk, b_tail) = split_chunk(b_rest);
+        if a_chunk.is_empty() || b_chunk.is_empty() {
+            return a_chunk.cmp(b_chunk).then_with(|| a.cmp(b));
+        }
+        let a_digits = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let b_digits = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = if a_digits && b_digits {
+            let a_num = a_chunk.trim_start_matches('0');
+            let b_num = b_chunk.trim_start_matches('0');
+            a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num))
+        } else {
+            a_chunk.cmp(b_chunk)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        (a_rest, b_rest) = (a_tail, b_tail);
+    }
+}
+
+/// Split off the leading run of either digits or non-digits.
+fn split_chunk(s: &str) -> (&str, &str) {
+    let digits = s.starts_with(|c: char| c.is_ascii_digit());
+    let end = s
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// The metadata used by the native sort modes. Entries whose metadata can't be
+/// read sort first; the walker reports the error when it reaches them.
+#[derive(Clone, Copy, Default)]
+struct MetadataKey {
+    is_dir: bool,
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+impl MetadataKey {
+    fn new(path: &Path, follow_symlinks: bool) -> Self {
+        let metadata = if follow_symlinks {
+            path.metadata()
+        } else {
+            path.symlink_metadata()
+        };
+        match metadata {
+            Ok(metadata) => Self {
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                mtime: metadata.modified().ok(),
+            },
+            Err(_) => Self::default(),
+        }
+    }
+}
+
+/// Caches a value for each entry of the directory being sorted.
+///
+/// The walker sorts all entries of a directory before yielding any of them,
+/// so each value is computed once and the cache is cleared for the next
+/// directory.
+struct PerDirCache<V> {
+    parent: Option<PathBuf>,
+    values: HashMap<PathBuf, V>,
+}
+
+impl<V> Default for PerDirCache<V> {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl<V: Clone> PerDirCache<V> {
+    fn get_or_insert(&mut self, path: &Path, f: impl FnOnce(&Path) -> V) -> V {
+        match self.try_get_or_insert(path, |path| Ok::<_, Infallible>(f(path))) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    fn try_get_or_insert<E>(
+        &mut self,
+        path: &Path,
+        f: impl FnOnce(&Path) -> Result<V, E>,
+    ) -> Result<V, E> {
+        if self.parent.as_deref() != path.parent() {
+            self.parent = path.parent().map(Path::to_path_buf);
+            self.values.clear();
+        }
+        if let Some(value) = self.values.get(path) {
+            return Ok(value.clone());
+        }
+        let value = f(path)?;
+        self.values.insert(path.to_path_buf(), value.clone());
+        Ok(value)
+    }
+}
+
+/// What a Python sort key is called with.
+pub(crate) enum KeyArg {
+    /// The path as `str`, or `bytes` if the walked paths are bytes.
+    Path,
+    /// A `DirEntry`, whose depth is computed relative to the given roots.
+    Entry { roots: Roots, follow_symlinks: bool },
+}
+
+/// Compares paths by a key which a Python callable computes for each entry.
+pub(crate) struct KeyComparator {
+    key: PyObject,
+    arg: KeyArg,
+    as_bytes: bool,
+    callback_error: CallbackError,
+    cache: Mutex<PerDirCache<Option<PyObject>>>,
+}
+
+impl KeyComparator {
+    pub(crate) fn new(
+        key: PyObject,
+        arg: KeyArg,
+        as_bytes: bool,
+        callback_error: CallbackError,
+    ) -> Self {
+        Self {
+            key,
+            arg,
+            as_bytes,
+            callback_error,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn compare(&self, a: &Path, b: &Path) -> Ordering {
+        self.try_compare(a, b).unwrap_or_else(|err| {
+            self.callback_error.set(err);
+            a.cmp(b)
+        })
+    }
+
+    fn try_compare(&self, a: &Path, b: &Path) -> PyResult<Ordering> {
+        Python::with_gil(|py| {
+            let mut cache = self.cache.lock().unwrap();
+            let ka = cache.try_get_or_insert(a, |path| self.key(py, path))?;
+            let kb = cache.try_get_or_insert(b, |path| self.key(py, path))?;
+            match (ka, kb) {
+                (Some(ka), Some(kb)) => compare_keys(ka.bind(py), kb.bind(py)),
+                // Like with MetadataKey, entries whose metadata can't be read
+                // sort first
+                (ka, kb) => Ok(ka.is_some().cmp(&kb.is_some()).then_with(|| a.cmp(b))),
+            }
+        })
+    }
+
+    /// Call the key for a path, or return `None` if it's a `DirEntry` key
+    /// and the path's metadata can't be read. The walker reports the error
+    /// when it reaches the entry, so it isn't raised here.
+    fn key(&self, py: Python<'_>, path: &Path) -> PyResult<Option<PyObject>> {
+        match &self.arg {
+            KeyArg::Path => self
+                .key
+                .call1(py, (path_to_object(py, path, self.as_bytes)?,))
+                .map(Some),
+            KeyArg::Entry {
+                roots,
+                follow_symlinks,
+            } => {
+                let entry = match DirEntry::from_path(
+                    path.to_path_buf(),
+                    depth(roots, path),
+                    roots.clone(),
+                    *follow_symlinks,
+                    self.as_bytes,
+                ) {
+                    Ok(entry) => entry,
+                    Err(_) => return Ok(None),
+                };
+                self.key.call1(py, (entry,)).map(Some)
+            }
+        }
+    }
+}
+
+/// The depth of a path below the first root which contains it. Only entries
+/// inside a root are sorted, but the root is ambiguous if the roots are nested.
+fn depth(roots: &Roots, path: &Path) -> usize {
+    roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .map_or(0, |relative| relative.components().count())
+}
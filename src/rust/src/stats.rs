@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use pyo3::prelude::*;
+
+use crate::matched::MatchInfo;
+use crate::walker::Entry;
+use crate::TYPES_MODULE;
+
+/// Counts accumulated while walking, converted to a `WalkStats` from
+/// `_types.py`.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    dirs: u64,
+    files: u64,
+    symlinks: u64,
+    errors: u64,
+    skipped_ignored: u64,
+    skipped_hidden: u64,
+    bytes: u64,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+}
+
+impl Stats {
+    pub fn start(&mut self) {
+        self.started = Some(Instant::now());
+    }
+
+    /// Stop the clock, if the walk started and hasn't already finished.
+    pub fn finish(&mut self) {
+        if self.started.is_some() && self.finished.is_none() {
+            self.finished = Some(Instant::now());
+        }
+    }
+
+    pub fn add_entry(&mut self, entry: &Entry) {
+        if let Some(file_type) = entry.file_type() {
+            if file_type.is_dir() {
+                self.dirs += 1;
+            } else if file_type.is_file() {
+                self.files += 1;
+            }
+        }
+        if entry.path_is_symlink() {
+            self.symlinks += 1;
+        }
+        if let Some(metadata) = &entry.metadata {
+            if metadata.is_file() {
+                self.bytes += metadata.len();
+            }
+        }
+    }
+
+    pub fn add_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn add_skipped(&mut self, mat: &MatchInfo) {
+        if mat.kind == "hidden" {
+            self.skipped_hidden += 1;
+        } else {
+            self.skipped_ignored += 1;
+        }
+    }
+
+    /// Convert to a `WalkStats`. The skipped entries and bytes are only
+    /// counted if `count_skipped` and `count_bytes` are true.
+    pub fn to_object(
+        &self,
+        py: Python<'_>,
+        count_skipped: bool,
+        count_bytes: bool,
+    ) -> PyResult<PyObject> {
+        let elapsed = match self.started {
+            Some(started) => self
+                .finished
+                .unwrap_or_else(Instant::now)
+                .duration_since(started)
+                .as_secs_f64(),
+            None => 0.0,
+        };
+        let types_module = TYPES_MODULE.get(py).unwrap().bind(py);
+        let stats = types_module.getattr("WalkStats")?.call1((
+            self.dirs,
+            self.files,
+            self.symlinks,
+            self.errors,
+            count_skipped.then_some(self.skipped_ignored),
+            count_skipped.then_some(self.skipped_hidden),
+            count_bytes.then_some(self.bytes),
+            elapsed,
+        ))?;
+        Ok(stats.unbind())
+    }
+}
@@ -1,13 +1,19 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+use ignore::types::TypesBuilder;
 use pyo3::basic::CompareOp;
 use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::pybacked::PyBackedStr;
-use pyo3::types::{PyDict, PyIterator, PyList, PyMapping, PySequence, PyString, PyTuple};
+use pyo3::types::{PyDict, PyIterator, PyList, PyMapping, PySequence, PyString, PyTuple, PyType};
 use pyo3::{PyTraverseError, PyVisit};
 use regex::Regex;
 
 use crate::error::IntoPyErr;
-use crate::util::Maybe;
+use crate::matched::MatchInfo;
+use crate::util::{fspath, Maybe};
 use crate::{ITEMS_VIEW_TYPE, KEYS_VIEW_TYPE, VALUES_VIEW_TYPE};
 
 impl<'py> FromPyObject<'py> for Maybe<Bound<'py, PyAny>> {
@@ -27,6 +33,9 @@ pub enum Selection {
 pub struct Types {
     types: Option<Py<PyDict>>,
     pub selections: Vec<Selection>,
+    // Compiled lazily by matched and type_of, and reset by any modification
+    matcher: RefCell<Option<ignore::types::Types>>,
+    all_matcher: RefCell<Option<ignore::types::Types>>,
 }
 
 #[pymethods]
@@ -41,6 +50,8 @@ impl Types {
         let instance = Self {
             types: Some(PyDict::new_bound(py).unbind()),
             selections: Vec::new(),
+            matcher: RefCell::new(None),
+            all_matcher: RefCell::new(None),
         };
         instance.update(py, initial, kwargs)?;
         Ok(instance)
@@ -84,27 +95,15 @@ impl Types {
     }
 
     pub fn keys(self_: PyRef<'_, Self>, py: Python<'_>) -> PyResult<PyObject> {
-        KEYS_VIEW_TYPE
-            .get(py)
-            .unwrap()
-            .call1(py, (self_,))
-            .map(Into::into)
+        KEYS_VIEW_TYPE.get(py).unwrap().call1(py, (self_,))
     }
 
     pub fn items(self_: PyRef<'_, Self>, py: Python<'_>) -> PyResult<PyObject> {
-        ITEMS_VIEW_TYPE
-            .get(py)
-            .unwrap()
-            .call1(py, (self_,))
-            .map(Into::into)
+        ITEMS_VIEW_TYPE.get(py).unwrap().call1(py, (self_,))
     }
 
     pub fn values(self_: PyRef<'_, Self>, py: Python<'_>) -> PyResult<PyObject> {
-        VALUES_VIEW_TYPE
-            .get(py)
-            .unwrap()
-            .call1(py, (self_,))
-            .map(Into::into)
+        VALUES_VIEW_TYPE.get(py).unwrap().call1(py, (self_,))
     }
 
     pub fn __richcmp__<'py>(
@@ -162,6 +161,7 @@ impl Types {
     }
 
     pub fn __delitem__(&self, py: Python<'_>, name: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.reset_matchers();
         self.types.as_ref().unwrap().bind(py).del_item(name)
     }
 
@@ -203,6 +203,7 @@ impl Types {
         &self,
         py: Python<'py>,
     ) -> PyResult<(Bound<'py, PyString>, Bound<'py, PyTuple>)> {
+        self.reset_matchers();
         let (name, globs): (Bound<'_, _>, Bound<'_, PyList>) = self
             .types
             .as_ref()
@@ -214,6 +215,7 @@ impl Types {
     }
 
     pub fn clear(&self, py: Python<'_>) {
+        self.reset_matchers();
         self.types.as_ref().unwrap().bind(py).clear()
     }
 
@@ -231,12 +233,20 @@ impl Types {
         if let Ok(other) = other.downcast::<PyMapping>() {
             for name in other.iter()? {
                 let name = &name?;
-                self.__setitem__(py, &*name.extract::<PyBackedStr>()?, other.get_item(name)?.downcast()?)?;
+                self.__setitem__(
+                    py,
+                    &name.extract::<PyBackedStr>()?,
+                    other.get_item(name)?.downcast()?,
+                )?;
             }
         } else if other.hasattr("keys")? {
             for name in other.call_method0("keys")?.iter()? {
                 let name = &name?;
-                self.__setitem__(py, &*name.extract::<PyBackedStr>()?, other.get_item(name)?.downcast()?)?;
+                self.__setitem__(
+                    py,
+                    &name.extract::<PyBackedStr>()?,
+                    other.get_item(name)?.downcast()?,
+                )?;
             }
         } else {
             for item in other.iter()? {
@@ -246,7 +256,7 @@ impl Types {
         }
         if let Some(kwargs) = kwargs {
             for (name, globs) in kwargs.iter() {
-                self.__setitem__(py, &*name.extract::<PyBackedStr>()?, &globs.extract()?)?;
+                self.__setitem__(py, &name.extract::<PyBackedStr>()?, &globs.extract()?)?;
             }
         }
         Ok(())
@@ -266,7 +276,7 @@ impl Types {
         match self.__getitem__(py, key) {
             Ok(globs) => Ok(globs),
             Err(err) if err.is_instance_bound(py, &py.get_type_bound::<PyKeyError>()) => {
-                self.__setitem__(py, &*key.extract::<PyBackedStr>()?, &default)?;
+                self.__setitem__(py, &key.extract::<PyBackedStr>()?, &default)?;
                 Ok(default.to_tuple()?)
             }
             Err(err) => Err(err),
@@ -280,6 +290,7 @@ impl Types {
         if name == "all" || !RE.is_match(name) {
             return Err(ignore::Error::InvalidDefinition.into_py_err(py));
         }
+        self.reset_matchers();
         let types = self.types.as_ref().unwrap().bind(py);
         let globs: Bound<'_, PyList> = match types.get_item(name)? {
             Some(globs) => globs.downcast_into()?,
@@ -293,6 +304,69 @@ impl Types {
         Ok(())
     }
 
+    /// Add a type definition in ripgrep's ``--type-add`` format, i.e.
+    /// ``name:glob`` or ``name:include:other,types``.
+    pub fn add_def(&self, py: Python<'_>, definition: &str) -> PyResult<()> {
+        let parts: Vec<&str> = definition.split(':').collect();
+        match parts[..] {
+            [name, glob] if !name.is_empty() && !glob.is_empty() => {
+                self.add(py, name, &PyString::new_bound(py, glob))
+            }
+            [name, "include", names] if !name.is_empty() && !names.is_empty() => {
+                let types = self.types.as_ref().unwrap().bind(py);
+                // Check all types exist before adding any globs
+                let mut globs = Vec::new();
+                for included in names.split(',') {
+                    match types.get_item(included)? {
+                        Some(included) => globs.extend(included.downcast_into::<PyList>()?),
+                        None => return Err(ignore::Error::InvalidDefinition.into_py_err(py)),
+                    }
+                }
+                for glob in globs {
+                    self.add(py, name, glob.downcast()?)?;
+                }
+                Ok(())
+            }
+            _ => Err(ignore::Error::InvalidDefinition.into_py_err(py)),
+        }
+    }
+
+    /// Create a ``Types`` instance from a file of type definitions.
+    #[classmethod]
+    pub fn from_file(
+        _cls: &Bound<'_, PyType>,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+    ) -> PyResult<Self> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let contents = fs::read_to_string(&path)?;
+        let instance = Self::new(py, None, None)?;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once(':') {
+                // The format used by ripgrep's --type-list, e.g. "py: *.py, *.pyi"
+                Some((name, globs)) if globs.starts_with(char::is_whitespace) => {
+                    globs.split(',').try_for_each(|glob| {
+                        instance.add(py, name, &PyString::new_bound(py, glob.trim()))
+                    })
+                }
+                _ => instance.add_def(py, line),
+            };
+            if let Err(err) = result {
+                let value = err.value_bound(py);
+                if value.hasattr("line")? {
+                    value.setattr("line", i + 1)?;
+                    value.setattr("path", &path)?;
+                }
+                return Err(err);
+            }
+        }
+        Ok(instance)
+    }
+
     pub fn add_defaults(&self, py: Python<'_>) -> PyResult<()> {
         lazy_static::lazy_static! {
             static ref DEFAULT_TYPES: Vec<ignore::types::FileTypeDef> =
@@ -314,6 +388,7 @@ impl Types {
     ///
     /// If `name` is `all`, then all file types currently defined are selected.
     pub fn select(&mut self, py: Python<'_>, name: &str) {
+        self.reset_matchers();
         if name == "all" {
             for name in self.types.as_ref().unwrap().bind(py).keys() {
                 self.selections.push(Selection::Select(name.to_string()));
@@ -327,6 +402,7 @@ impl Types {
     ///
     /// If `name` is `all`, then all file types currently defined are negated.
     pub fn negate(&mut self, py: Python<'_>, name: &str) {
+        self.reset_matchers();
         if name == "all" {
             for name in self.types.as_ref().unwrap().bind(py).keys() {
                 self.selections.push(Selection::Negate(name.to_string()));
@@ -336,6 +412,43 @@ impl Types {
         }
     }
 
+    /// Match ``path`` against the selected and negated file types.
+    #[pyo3(signature = (path, is_dir = false))]
+    pub fn matched(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        is_dir: bool,
+    ) -> PyResult<PyObject> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let mut matcher = self.matcher.borrow_mut();
+        if matcher.is_none() {
+            *matcher = Some(self.build(py)?);
+        }
+        let mat = matcher.as_ref().unwrap().matched(&path, is_dir);
+        match MatchInfo::from_types(mat, &path) {
+            Some(mat) => mat.into_py(py),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Return the file type definition ``path`` belongs to, regardless of
+    /// selections.
+    pub fn type_of(&self, py: Python<'_>, path: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let path: PathBuf = fspath(path)?.extract()?;
+        let mut all_matcher = self.all_matcher.borrow_mut();
+        if all_matcher.is_none() {
+            let mut types_builder = self.builder(py)?;
+            types_builder.select("all");
+            *all_matcher = Some(types_builder.build().map_err(|err| err.into_py_err(py))?);
+        }
+        let mat = all_matcher.as_ref().unwrap().matched(&path, false);
+        match MatchInfo::from_types(mat, &path) {
+            Some(mat) if mat.file_type.is_some() => mat.into_py(py),
+            _ => Ok(py.None()),
+        }
+    }
+
     fn __getnewargs__(&self) -> (Py<PyDict>,) {
         (self.types.clone().unwrap(),)
     }
@@ -356,6 +469,7 @@ impl Types {
 
     fn __setstate__(&mut self, py: Python<'_>, state: Py<PyDict>) -> PyResult<()> {
         let state = state.bind(py);
+        self.reset_matchers();
         let selections = <Bound<'_, PyAny>>::get_item(state, "selections")?;
         let selections: Vec<(String, String)> = selections.extract()?;
         self.selections = selections
@@ -380,3 +494,41 @@ impl Types {
         self.types = None;
     }
 }
+
+impl Types {
+    /// Compile the type definitions and selections into an `ignore` matcher.
+    pub fn build(&self, py: Python<'_>) -> PyResult<ignore::types::Types> {
+        let mut types_builder = self.builder(py)?;
+        for selection in &self.selections {
+            match selection {
+                Selection::Select(name) => {
+                    types_builder.select(name);
+                }
+                Selection::Negate(name) => {
+                    types_builder.negate(name);
+                }
+            }
+        }
+        types_builder.build().map_err(|err| err.into_py_err(py))
+    }
+
+    /// Create a builder with the type definitions, but no selections.
+    fn builder(&self, py: Python<'_>) -> PyResult<TypesBuilder> {
+        let mut types_builder = TypesBuilder::new();
+        for name in self.__iter__(py)?.bind(py) {
+            let name = name?;
+            let globs: Py<PyTuple> = self.__getitem__(py, &name)?.extract()?;
+            for glob in globs.extract::<Vec<PyBackedStr>>(py)? {
+                types_builder
+                    .add(&name.extract::<PyBackedStr>()?, &glob)
+                    .map_err(|err| err.into_py_err(py))?;
+            }
+        }
+        Ok(types_builder)
+    }
+
+    fn reset_matchers(&self) {
+        self.matcher.replace(None);
+        self.all_matcher.replace(None);
+    }
+}
@@ -1,8 +1,11 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::Path;
 
+use pyo3::exceptions::PyTypeError;
 use pyo3::ffi;
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyBytes, PyList};
 
 pub fn fspath<'py>(path: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
     let py = path.py();
@@ -16,10 +19,120 @@ pub fn fspath<'py>(path: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
 pub fn fspath_list(paths: &Bound<'_, PyList>) -> PyResult<Vec<OsString>> {
     paths
         .iter()
-        .map(|path| fspath(&path).and_then(|p| p.extract()))
+        .map(|path| fspath(&path).and_then(|p| extract_os_string(&p)))
         .collect()
 }
 
+/// Like `fspath_list`, but also return whether the paths are `bytes`. Mixing
+/// `str` and `bytes` paths is an error.
+pub fn fspath_list_bytes(paths: &Bound<'_, PyList>) -> PyResult<(Vec<OsString>, bool)> {
+    let mut is_bytes = None;
+    let mut result = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let path = fspath(&path)?;
+        let path_is_bytes = path.is_instance_of::<PyBytes>();
+        if *is_bytes.get_or_insert(path_is_bytes) != path_is_bytes {
+            return Err(PyTypeError::new_err("Can't mix strings and bytes in paths"));
+        }
+        result.push(extract_os_string(&path)?);
+    }
+    Ok((result, is_bytes.unwrap_or(false)))
+}
+
+/// Extract a path returned by `fspath`, which may be `str` or `bytes`.
+pub fn extract_os_string(path: &Bound<'_, PyAny>) -> PyResult<OsString> {
+    match path.downcast::<PyBytes>() {
+        Ok(bytes) => bytes_to_os_string(bytes),
+        Err(_) => path.extract(),
+    }
+}
+
+/// Convert bytes to a path, like `os.fsdecode`.
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &Bound<'_, PyBytes>) -> PyResult<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(OsStr::from_bytes(bytes.as_bytes()).to_os_string())
+}
+
+/// Convert bytes to a path, like `os.fsdecode`.
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &Bound<'_, PyBytes>) -> PyResult<OsString> {
+    let py = bytes.py();
+    let data = bytes.as_bytes();
+    unsafe {
+        let ptr = ffi::PyUnicode_DecodeFSDefaultAndSize(data.as_ptr().cast(), data.len() as _);
+        Bound::from_owned_ptr_or_err(py, ptr)?.extract()
+    }
+}
+
+/// Convert a path to `bytes` if `as_bytes` is true, otherwise to `str`.
+pub fn path_to_object(py: Python<'_>, path: &Path, as_bytes: bool) -> PyResult<PyObject> {
+    if as_bytes {
+        Ok(path_to_bytes(py, path)?.into_any().unbind())
+    } else {
+        Ok(path.as_os_str().to_object(py))
+    }
+}
+
+/// Convert a path to bytes, like `os.fsencode`.
+#[cfg(unix)]
+pub fn path_to_bytes<'py>(py: Python<'py>, path: &Path) -> PyResult<Bound<'py, PyBytes>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(PyBytes::new_bound(py, path.as_os_str().as_bytes()))
+}
+
+/// Convert a path to bytes, like `os.fsencode`.
+#[cfg(not(unix))]
+pub fn path_to_bytes<'py>(py: Python<'py>, path: &Path) -> PyResult<Bound<'py, PyBytes>> {
+    let path = path.to_object(py);
+    unsafe {
+        let ptr = ffi::PyUnicode_EncodeFSDefault(path.as_ptr());
+        Ok(Bound::from_owned_ptr_or_err(py, ptr)?.downcast_into_unchecked())
+    }
+}
+
+/// The fields of a file's metadata which the standard library only exposes on
+/// Unix.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub struct UnixMetadata {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub blocks: u64,
+}
+
+/// Read the Unix-only fields of `metadata`.
+///
+/// Device and inode numbers, permissions, owners, link counts and allocated
+/// blocks aren't available from the standard library on other platforms, so
+/// this returns `None` there and callers fall back to behaviour which doesn't
+/// need them.
+#[cfg(unix)]
+pub fn unix_metadata(metadata: &fs::Metadata) -> Option<UnixMetadata> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(UnixMetadata {
+        dev: metadata.dev(),
+        ino: metadata.ino(),
+        mode: metadata.mode(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        nlink: metadata.nlink(),
+        blocks: metadata.blocks(),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn unix_metadata(_metadata: &fs::Metadata) -> Option<UnixMetadata> {
+    None
+}
+
 /// Similar to Option but the pyo3 conversion traits are not implemented for it, so we can use
 /// it as a default argument and know that it wasn't passed.
 #[derive(Clone, Debug)]
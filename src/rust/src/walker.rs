@@ -0,0 +1,550 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, FileType};
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use ignore::WalkState;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+
+use crate::direntry::{DirEntry, Roots};
+use crate::metadata_filter::MetadataFilter;
+use crate::util::unix_metadata;
+
+/// Maximum number of entries the parallel walker may produce before the
+/// consumer has to catch up.
+const CHANNEL_CAPACITY: usize = 1024;
+
+pub(crate) type IgnoreResult = Result<ignore::DirEntry, ignore::Error>;
+
+pub(crate) type DentResult = Result<Dent, ignore::Error>;
+
+pub(crate) type WalkResult = Result<Entry, ignore::Error>;
+
+/// A directory entry found by one of the walkers.
+///
+/// `ignore::DirEntry` can only be created by the ignore crate, so entries
+/// found by `BreadthFirst`, or created from a path for a `sort_key`, are a
+/// `PathEntry` with the same interface.
+#[derive(Clone, Debug)]
+pub(crate) enum Dent {
+    Walk(ignore::DirEntry),
+    Path(PathEntry),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PathEntry {
+    pub path: PathBuf,
+    pub depth: usize,
+    // The type of the symlink's target if `follow_link` is true
+    pub file_type: FileType,
+    pub is_symlink: bool,
+    pub follow_link: bool,
+    #[cfg(unix)]
+    pub ino: u64,
+    // An error reading the ignore files of a directory
+    pub err: Option<ignore::Error>,
+}
+
+impl PathEntry {
+    /// Create an entry for a path, reading its metadata like the walker does,
+    /// which follows a symlink if `follow_link` is true. The metadata is also
+    /// returned.
+    pub(crate) fn from_path(
+        path: &Path,
+        depth: usize,
+        follow_link: bool,
+    ) -> io::Result<(Self, fs::Metadata)> {
+        let mut metadata = path.symlink_metadata()?;
+        let is_symlink = metadata.file_type().is_symlink();
+        if is_symlink && follow_link {
+            metadata = path.metadata()?;
+        }
+        let entry = Self {
+            path: path.to_path_buf(),
+            depth,
+            file_type: metadata.file_type(),
+            is_symlink,
+            follow_link,
+            #[cfg(unix)]
+            ino: std::os::unix::fs::MetadataExt::ino(&metadata),
+            err: None,
+        };
+        Ok((entry, metadata))
+    }
+}
+
+impl Dent {
+    pub(crate) fn path(&self) -> &Path {
+        match self {
+            Dent::Walk(dent) => dent.path(),
+            Dent::Path(entry) => &entry.path,
+        }
+    }
+
+    pub(crate) fn file_name(&self) -> &OsStr {
+        match self {
+            Dent::Walk(dent) => dent.file_name(),
+            Dent::Path(entry) => entry.path.file_name().unwrap_or(entry.path.as_os_str()),
+        }
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        match self {
+            Dent::Walk(dent) => dent.depth(),
+            Dent::Path(entry) => entry.depth,
+        }
+    }
+
+    /// The file type, which is `None` only for stdin, which is never walked.
+    pub(crate) fn file_type(&self) -> Option<FileType> {
+        match self {
+            Dent::Walk(dent) => dent.file_type(),
+            Dent::Path(entry) => Some(entry.file_type),
+        }
+    }
+
+    pub(crate) fn path_is_symlink(&self) -> bool {
+        match self {
+            Dent::Walk(dent) => dent.path_is_symlink(),
+            Dent::Path(entry) => entry.is_symlink,
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn ino(&self) -> Option<u64> {
+        match self {
+            Dent::Walk(dent) => dent.ino(),
+            Dent::Path(entry) => Some(entry.ino),
+        }
+    }
+
+    pub(crate) fn error(&self) -> Option<&ignore::Error> {
+        match self {
+            Dent::Walk(dent) => dent.error(),
+            Dent::Path(entry) => entry.err.as_ref(),
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> Result<fs::Metadata, ignore::Error> {
+        match self {
+            Dent::Walk(dent) => dent.metadata(),
+            Dent::Path(entry) => {
+                let metadata = if entry.follow_link {
+                    entry.path.metadata()
+                } else {
+                    entry.path.symlink_metadata()
+                };
+                metadata.map_err(|err| io_error(err, &entry.path))
+            }
+        }
+    }
+}
+
+/// An I/O error for a path, with the message walkdir gives it, as the ignore
+/// crate reports errors reading the entries it walks.
+pub(crate) fn io_error(err: io::Error, path: &Path) -> ignore::Error {
+    let message = format!("IO error for operation on {}: {}", path.display(), err);
+    ignore::Error::WithPath {
+        path: path.to_path_buf(),
+        err: Box::new(ignore::Error::Io(io::Error::new(err.kind(), message))),
+    }
+}
+
+/// An entry produced by the walker, with its metadata if it was prefetched.
+pub(crate) struct Entry {
+    pub dent: Dent,
+    // Boxed because it's large compared to the rest of a `WalkResult`
+    pub metadata: Option<Box<fs::Metadata>>,
+}
+
+impl Entry {
+    /// Wrap a result from the walker, reading the entry's metadata first if
+    /// `prefetch` says so, so that it's done without the GIL.
+    pub(crate) fn from_result(result: DentResult, prefetch: Prefetch) -> WalkResult {
+        result.map(|dent| {
+            let read = match prefetch {
+                Prefetch::Never => false,
+                Prefetch::Files => dent.file_type().map_or(false, |ft| ft.is_file()),
+                Prefetch::Always => true,
+            };
+            let metadata = if read {
+                dent.metadata().ok().map(Box::new)
+            } else {
+                None
+            };
+            Entry { dent, metadata }
+        })
+    }
+}
+
+/// Which entries the walker reads the metadata of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Prefetch {
+    Never,
+    /// Only files, whose sizes are counted by `count_bytes`.
+    Files,
+    /// Every entry, for `prefetch_metadata`.
+    Always,
+}
+
+impl Prefetch {
+    pub(crate) fn new(prefetch_metadata: bool, count_bytes: bool) -> Self {
+        if prefetch_metadata {
+            Prefetch::Always
+        } else if count_bytes {
+            Prefetch::Files
+        } else {
+            Prefetch::Never
+        }
+    }
+}
+
+impl Deref for Entry {
+    type Target = Dent;
+
+    fn deref(&self) -> &Dent {
+        &self.dent
+    }
+}
+
+/// The files yielded so far, identified by their device and inode numbers,
+/// so that `unique_inodes` yields each file once.
+#[derive(Clone, Default)]
+pub(crate) struct SeenInodes(Arc<Mutex<HashSet<(u64, u64)>>>);
+
+impl SeenInodes {
+    /// Whether `result` should be yielded, because it's an error or the first
+    /// entry seen for its file.
+    pub(crate) fn is_first(&self, result: &WalkResult) -> bool {
+        match result.as_ref().ok().and_then(file_id) {
+            Some(id) => self.0.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+}
+
+/// The device and inode numbers of an entry, or `None` if its metadata can't
+/// be read. Entries are read again unless their metadata was prefetched.
+fn file_id(entry: &Entry) -> Option<(u64, u64)> {
+    // Without inode numbers, every entry is yielded
+    let id = |metadata: &fs::Metadata| unix_metadata(metadata).map(|md| (md.dev, md.ino));
+    match &entry.metadata {
+        Some(metadata) => id(metadata),
+        None => id(&entry.dent.metadata().ok()?),
+    }
+}
+
+/// Stores the first error raised by a Python callback (e.g. `filter_entry`)
+/// invoked from inside the walker, so that it can be raised from `__next__`.
+///
+/// This can't rely on the thread's error indicator because callbacks may run
+/// on the parallel walker's threads.
+#[derive(Clone, Default)]
+pub(crate) struct CallbackError(Arc<Mutex<Option<PyErr>>>);
+
+impl CallbackError {
+    pub(crate) fn set(&self, err: PyErr) {
+        let mut slot = self.0.lock().unwrap();
+        if slot.is_none() {
+            *slot = Some(err);
+        }
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    pub(crate) fn take(&self) -> Option<PyErr> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Decides whether the walker yields an entry, and descends into it if it's a
+/// directory. `filter_entry` is called last, so that it only sees entries
+/// which would otherwise be yielded.
+#[derive(Clone)]
+pub(crate) struct EntryFilter {
+    pub skip_dir: Option<SkipDir>,
+    pub metadata_filter: MetadataFilter,
+    pub filter_entry: Option<PyObject>,
+    pub roots: Roots,
+    pub follow_symlinks: bool,
+    pub as_bytes: bool,
+    pub callback_error: CallbackError,
+}
+
+impl EntryFilter {
+    pub(crate) fn accepts(&self, dent: &Dent) -> bool {
+        if let Some(skip_dir) = &self.skip_dir {
+            if skip_dir.contains(dent.path()) {
+                return false;
+            }
+        }
+        if self.metadata_filter.rejects_entry(dent).is_some() {
+            return false;
+        }
+        let filter_entry = match &self.filter_entry {
+            Some(filter_entry) => filter_entry,
+            None => return true,
+        };
+        let py_dent = DirEntry::new(
+            dent.clone(),
+            self.roots.clone(),
+            self.follow_symlinks,
+            self.as_bytes,
+            None,
+        );
+        Python::with_gil(|py| {
+            filter_entry
+                .call1(py, (py_dent,))
+                .and_then(|result| result.is_truthy(py))
+                .unwrap_or_else(|err| {
+                    self.callback_error.set(err);
+
+                    // Return true so that we reach the __next__ method where we can return
+                    // the error can be raised
+                    true
+                })
+        })
+    }
+}
+
+/// The directory whose remaining entries should be skipped, set by
+/// `Walk.skip_current_dir` and checked by the walker's entry filter.
+#[derive(Clone, Default)]
+pub(crate) struct SkipDir(Arc<Mutex<Option<PathBuf>>>);
+
+impl SkipDir {
+    pub(crate) fn set(&self, dir: PathBuf) {
+        *self.0.lock().unwrap() = Some(dir);
+    }
+
+    /// Whether `path` is inside the skipped directory.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        match &*self.0.lock().unwrap() {
+            Some(dir) => path != dir && path.starts_with(dir),
+            None => false,
+        }
+    }
+}
+
+pub(crate) type SequentialWalker = Box<dyn Iterator<Item = WalkResult> + Send>;
+
+pub(crate) enum Walker {
+    Sequential(SequentialWalker),
+    Threaded(ThreadedWalker),
+}
+
+impl Iterator for Walker {
+    type Item = WalkResult;
+
+    fn next(&mut self) -> Option<WalkResult> {
+        match self {
+            Walker::Sequential(walk) => walk.next(),
+            Walker::Threaded(walk) => walk.next(),
+        }
+    }
+}
+
+/// Yields each directory after its contents instead of before.
+pub(crate) struct ContentsFirst<I> {
+    inner: I,
+    // Directories which may have more contents to yield, deepest last
+    pending: Vec<ignore::DirEntry>,
+    // Pulled from `inner` but must wait until finished directories are yielded
+    queued: Option<ignore::DirEntry>,
+}
+
+impl<I> ContentsFirst<I> {
+    pub(crate) fn new(inner: I) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+            queued: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = IgnoreResult>> Iterator for ContentsFirst<I> {
+    type Item = IgnoreResult;
+
+    fn next(&mut self) -> Option<IgnoreResult> {
+        loop {
+            let dent = match self.queued.take().map(Ok).or_else(|| self.inner.next()) {
+                Some(Ok(dent)) => dent,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return self.pending.pop().map(Ok),
+            };
+            // The walk is depth first, so any directory at the same depth or
+            // deeper has no more contents.
+            if self
+                .pending
+                .last()
+                .map_or(false, |dir| dir.depth() >= dent.depth())
+            {
+                self.queued = Some(dent);
+                return self.pending.pop().map(Ok);
+            }
+            if dent.file_type().map_or(false, |ft| ft.is_dir()) {
+                self.pending.push(dent);
+            } else {
+                return Some(Ok(dent));
+            }
+        }
+    }
+}
+
+/// The result of polling a `ThreadedWalker` without blocking.
+pub(crate) enum Poll {
+    Ready(Option<WalkResult>),
+    Pending,
+}
+
+/// Schedules a callback on an asyncio event loop from another thread, once
+/// the walker has produced another result.
+pub(crate) struct Waker {
+    call_soon_threadsafe: PyObject,
+    args: Py<PyTuple>,
+}
+
+impl Waker {
+    pub(crate) fn new(call_soon_threadsafe: PyObject, args: Py<PyTuple>) -> Self {
+        Self {
+            call_soon_threadsafe,
+            args,
+        }
+    }
+
+    fn wake(self) {
+        Python::with_gil(|py| {
+            // Fails if the event loop was closed, in which case nobody is
+            // waiting for the result anyway.
+            let _ = self.call_soon_threadsafe.call1(py, self.args.bind(py));
+        });
+    }
+}
+
+type WakerSlot = Arc<Mutex<Option<Waker>>>;
+
+/// Runs an `ignore::Walk` or `ignore::WalkParallel` on a background thread and
+/// receives its results through a bounded channel.
+pub(crate) struct ThreadedWalker {
+    receiver: Option<Receiver<WalkResult>>,
+    waker: WakerSlot,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ThreadedWalker {
+    pub(crate) fn sequential(walk: SequentialWalker) -> Self {
+        Self::spawn(move |sender, waker| {
+            for result in walk {
+                if sender.send(result).is_err() {
+                    // The receiver was dropped, so nobody is listening anymore
+                    break;
+                }
+                wake(waker);
+            }
+        })
+    }
+
+    pub(crate) fn parallel(
+        walk: ignore::WalkParallel,
+        prefetch: Prefetch,
+        seen_inodes: Option<SeenInodes>,
+    ) -> Self {
+        Self::spawn(move |sender, waker| {
+            walk.run(|| {
+                let sender = sender.clone();
+                let seen_inodes = seen_inodes.clone();
+                Box::new(move |result| {
+                    let result = Entry::from_result(result.map(Dent::Walk), prefetch);
+                    if let Some(seen_inodes) = &seen_inodes {
+                        if !seen_inodes.is_first(&result) {
+                            return WalkState::Continue;
+                        }
+                    }
+                    match sender.send(result) {
+                        Ok(()) => {
+                            wake(waker);
+                            WalkState::Continue
+                        }
+                        // The receiver was dropped, so nobody is listening anymore
+                        Err(_) => WalkState::Quit,
+                    }
+                })
+            })
+        })
+    }
+
+    fn spawn<F>(run: F) -> Self
+    where
+        F: FnOnce(&SyncSender<WalkResult>, &WakerSlot) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let waker = WakerSlot::default();
+        let thread_waker = Arc::clone(&waker);
+        let handle = thread::spawn(move || {
+            run(&sender, &thread_waker);
+            // Disconnect before waking so that the final poll sees the end
+            drop(sender);
+            wake(&thread_waker);
+        });
+        Self {
+            receiver: Some(receiver),
+            waker,
+            handle: Some(handle),
+        }
+    }
+
+    /// Return the next result if one is available, otherwise arrange for
+    /// `waker` to be woken when there is.
+    pub(crate) fn poll_next(&self, waker: Waker) -> Poll {
+        let receiver = match &self.receiver {
+            Some(receiver) => receiver,
+            None => return Poll::Ready(None),
+        };
+        // Holding the lock while checking the channel means the walker thread
+        // can't send a result in between without seeing the waker.
+        let mut slot = self.waker.lock().unwrap();
+        match receiver.try_recv() {
+            Ok(result) => Poll::Ready(Some(result)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                *slot = Some(waker);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn wake(waker: &WakerSlot) {
+    let waker = waker.lock().unwrap().take();
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+impl Iterator for ThreadedWalker {
+    type Item = WalkResult;
+
+    fn next(&mut self) -> Option<WalkResult> {
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for ThreadedWalker {
+    fn drop(&mut self) {
+        // Disconnecting the channel makes the walker quit at its next entry,
+        // including if it's blocked on a full channel.
+        self.receiver = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,100 @@
+import os
+from pathlib import Path
+
+import pytest
+
+from crabwalk import DiskUsage, disk_usage
+
+from .tree import Directory, File
+
+TREE = Directory(
+    "root",
+    File(".gitignore", "*.log\n"),
+    File("foo", "abc"),
+    File("debug.log", "ignored"),
+    Directory(
+        "a",
+        File("bar", "de"),
+        Directory("b"),
+    ),
+)
+
+
+def expected_usage(*paths: str) -> DiskUsage:
+    stats = [os.lstat(path) for path in paths]
+    return DiskUsage(
+        apparent_size=sum(stat.st_size for stat in stats),
+        allocated_size=sum(stat.st_blocks * 512 for stat in stats),
+        files=sum(not os.path.isdir(path) for path in paths),
+        dirs=sum(os.path.isdir(path) for path in paths[1:]),
+    )
+
+
+@pytest.mark.tree(TREE, chdir=True)
+@pytest.mark.parametrize("threads", [None, 2], ids=lambda threads: f"threads={threads}")
+def test_disk_usage(tree_path: Path, threads: "int | None") -> None:
+    usage = disk_usage("root", require_git=False, threads=threads)
+    assert list(usage) == ["root", "root/a", "root/a/b"]
+    assert usage["root"] == expected_usage(
+        "root", "root/foo", "root/a", "root/a/bar", "root/a/b"
+    )
+    assert usage["root/a"] == expected_usage("root/a", "root/a/bar", "root/a/b")
+    assert usage["root/a/b"] == expected_usage("root/a/b")
+    assert usage["root"].files == 2
+    assert usage["root"].dirs == 2
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_disk_usage_file_and_bytes(tree_path: Path) -> None:
+    assert disk_usage("root/foo") == {"root/foo": expected_usage("root/foo")}
+    assert list(disk_usage(b"root/a")) == [b"root/a", b"root/a/b"]
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_disk_usage_walk_options(tree_path: Path) -> None:
+    usage = disk_usage("root", max_depth=1, hidden=False, git_ignore=False)
+    assert list(usage) == ["root", "root/a"]
+    assert usage["root"].files == 3
+
+    errors = []
+    assert disk_usage("missing", onerror=errors.append) == {}
+    assert len(errors) == 1
+
+    with pytest.raises(TypeError):
+        disk_usage("root", unknown=True)
+
+
+@pytest.mark.tree(TREE, chdir=True)
+@pytest.mark.parametrize("threads", [None, 2], ids=lambda threads: f"threads={threads}")
+@pytest.mark.parametrize(
+    ("root", "keys"),
+    [
+        ("root/", ["root", "root/a", "root/a/b"]),
+        ("./root/a/", ["./root/a", "./root/a/b"]),
+    ],
+)
+def test_disk_usage_trailing_slash(
+    tree_path: Path, threads: "int | None", root: str, keys: list[str]
+) -> None:
+    usage = disk_usage(root, threads=threads)
+    assert list(usage) == keys
+    assert usage[keys[0]] == disk_usage(root.rstrip("/"))[keys[0]]
+
+
+@pytest.mark.tree(TREE, chdir=True)
+@pytest.mark.parametrize(
+    "option",
+    [
+        "sort",
+        "sort_key",
+        "reverse",
+        "contents_first",
+        "order",
+        "prefetch_metadata",
+        "count_skipped",
+        "count_bytes",
+    ],
+)
+def test_disk_usage_unsupported_options(tree_path: Path, option: str) -> None:
+    with pytest.raises(TypeError, match=f"unsupported keyword argument '{option}'"):
+        disk_usage("root", **{option: True})
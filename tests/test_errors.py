@@ -114,6 +114,32 @@ def test_invalid_definition_error(tmp_path: Path) -> None:
     )
 
 
+@pytest.mark.parametrize(
+    "definition", ["foo", "foo:", ":*.foo", "foo:a:b", "foo:include:bar"]
+)
+def test_invalid_definition_error_add_def(definition: str) -> None:
+    types = Types()
+
+    with pytest.raises(InvalidDefinitionError):
+        types.add_def(definition)
+
+    assert "foo" not in types
+
+
+def test_invalid_definition_error_from_file(tmp_path: Path) -> None:
+    path = tmp_path / "types"
+    path.write_text("py:*.py\n\nweb:include:html\n")
+
+    with pytest.raises(InvalidDefinitionError) as exc_info:
+        Types.from_file(path)
+
+    assert exc_info.value.line == 3
+    assert exc_info.value.path == str(path)
+    assert str(exc_info.value) == (
+        f"{path}: line 3: invalid definition (format is type:glob, e.g. html:*.html)"
+    )
+
+
 def test_partial_error(tmp_path: Path) -> None:
     ignore = tmp_path / "myignore"
     ignore.write_text("{\na{")
@@ -139,3 +165,9 @@ def test_partial_error(tmp_path: Path) -> None:
         "missing '}' (maybe escape '{' with '[{]'?)"
     )
     assert exc_info.value.errors[1].line == 2
+
+
+def test_overrides_compile_glob_error() -> None:
+    with pytest.raises(GlobError) as exc_info:
+        Overrides(["{"], path=".").compile()
+    assert exc_info.value.glob == "{"
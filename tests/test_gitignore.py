@@ -0,0 +1,85 @@
+from pathlib import Path
+
+import pytest
+
+from crabwalk import GlobError, Gitignore, Match, PartialError
+
+
+def test_add_line() -> None:
+    gitignore = Gitignore("root")
+    gitignore.add_line("*.log")
+    gitignore.add_line("!keep.log", source="root/.gitignore")
+    assert gitignore.path == "root"
+    assert gitignore.num_ignores == 1
+    assert gitignore.num_whitelists == 1
+
+    assert gitignore.matched("root/debug.log") == Match(
+        kind="gitignore", whitelist=False, glob="*.log"
+    )
+    assert gitignore.matched("root/keep.log") == Match(
+        kind="gitignore",
+        whitelist=True,
+        glob="!keep.log",
+        path="root/.gitignore",
+    )
+    assert gitignore.matched("root/main.py") is None
+
+
+def test_add_line_glob_error() -> None:
+    gitignore = Gitignore("root")
+    with pytest.raises(GlobError) as exc_info:
+        gitignore.add_line("{")
+    assert exc_info.value.glob == "{"
+
+
+def test_add(tmp_path: Path) -> None:
+    path = tmp_path / ".gitignore"
+    path.write_text("# comment\nbuild/\n")
+    gitignore = Gitignore(tmp_path)
+    gitignore.add(path)
+
+    assert gitignore.matched(tmp_path / "build") is None
+    assert gitignore.matched(tmp_path / "build", is_dir=True) == Match(
+        kind="gitignore", whitelist=False, glob="build/", path=str(path), line=2
+    )
+
+
+def test_add_glob_error(tmp_path: Path) -> None:
+    path = tmp_path / ".gitignore"
+    path.write_text("foo\n{\n")
+    gitignore = Gitignore(tmp_path)
+    with pytest.raises(GlobError) as exc_info:
+        gitignore.add(path)
+    assert exc_info.value.line == 2
+    assert exc_info.value.path == str(path)
+
+    # Valid globs are still added
+    assert gitignore.matched(tmp_path / "foo") is not None
+
+    path.write_text("{\n[\n")
+    with pytest.raises(PartialError):
+        gitignore.add(path)
+
+
+def test_add_missing(tmp_path: Path) -> None:
+    gitignore = Gitignore(tmp_path)
+    with pytest.raises(FileNotFoundError):
+        gitignore.add(tmp_path / ".gitignore")
+
+
+def test_case_insensitive() -> None:
+    gitignore = Gitignore("root", case_insensitive=True)
+    gitignore.add_line("*.LOG")
+    assert gitignore.matched("root/debug.log") is not None
+
+
+def test_matched_path_or_any_parents() -> None:
+    gitignore = Gitignore("/root")
+    gitignore.add_line("build/")
+    assert gitignore.matched("/root/build/out") is None
+    match = gitignore.matched_path_or_any_parents("/root/build/out")
+    assert match is not None
+    assert match.glob == "build/"
+
+    with pytest.raises(ValueError):
+        gitignore.matched_path_or_any_parents("/elsewhere/build/out")
@@ -0,0 +1,36 @@
+import pytest
+
+from crabwalk import CompiledOverrides, Match, Overrides
+
+
+def test_compile() -> None:
+    overrides = Overrides(["*.py", "!setup.py", ("*.MD", True)], path="root")
+    compiled = overrides.compile()
+    assert isinstance(compiled, CompiledOverrides)
+    assert compiled.path == "root"
+    assert compiled.num_ignores == 1
+    assert compiled.num_whitelists == 2
+
+    assert compiled.matched("root/main.py") == Match(
+        kind="override", whitelist=True, glob="*.py"
+    )
+    assert compiled.matched("root/setup.py") == Match(
+        kind="override", whitelist=False, glob="!setup.py"
+    )
+    assert compiled.matched("root/README.md") == Match(
+        kind="override", whitelist=True, glob="*.MD"
+    )
+    # Files which aren't whitelisted are ignored
+    assert compiled.matched("root/main.rs") == Match(kind="override", whitelist=False)
+    # but directories aren't
+    assert compiled.matched("root/src", is_dir=True) is None
+
+
+def test_compile_empty() -> None:
+    compiled = Overrides(path="root").compile()
+    assert compiled.matched("root/main.py") is None
+
+
+def test_compiled_overrides_type_error() -> None:
+    with pytest.raises(TypeError):
+        CompiledOverrides(["*.py"])  # type: ignore[arg-type]
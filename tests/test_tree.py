@@ -0,0 +1,195 @@
+import os
+from pathlib import Path
+
+import pytest
+
+from crabwalk import DirEntry, LoopError, walk_tree
+
+from .tree import Directory, File, Symlink
+
+TREE = Directory(
+    "root",
+    File("foo"),
+    Directory(
+        "a",
+        File("bar"),
+        Directory(
+            "b",
+            File("spam"),
+        ),
+    ),
+    Directory(
+        "c",
+        File("eggs"),
+    ),
+)
+
+
+def sorted_walk(
+    walk: "list[tuple[str, list[str], list[str]]]",
+) -> "list[tuple[str, list[str], list[str]]]":
+    return sorted((path, sorted(dirs), sorted(files)) for path, dirs, files in walk)
+
+
+@pytest.mark.tree(TREE, chdir=True)
+@pytest.mark.parametrize("topdown", [True, False])
+def test_walk_tree(tree_path: Path, topdown: bool) -> None:
+    result = list(walk_tree("root", topdown=topdown))
+    assert sorted_walk(result) == sorted_walk(list(os.walk("root")))
+    paths = [path for path, _, _ in result]
+    if topdown:
+        assert paths.index("root") < paths.index("root/a") < paths.index("root/a/b")
+    else:
+        assert paths.index("root/a/b") < paths.index("root/a") < paths.index("root")
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_walk_tree_prune(tree_path: Path) -> None:
+    paths = []
+    for dirpath, dirnames, _ in walk_tree("root"):
+        paths.append(dirpath)
+        if "a" in dirnames:
+            dirnames.remove("a")
+    assert sorted(paths) == ["root", "root/c"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File(".gitignore", "*.log\nbuild/\n"),
+        File(".hidden"),
+        File("main.py"),
+        File("debug.log"),
+        Directory(
+            "build",
+            File("out"),
+        ),
+        Directory(
+            "src",
+            File("lib.py"),
+            File("lib.log"),
+        ),
+    ),
+    chdir=True,
+)
+def test_walk_tree_filters(tree_path: Path) -> None:
+    result = sorted_walk(list(walk_tree("root", require_git=False)))
+    assert result == [
+        ("root", ["src"], ["main.py"]),
+        ("root/src", [], ["lib.py"]),
+    ]
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_walk_tree_max_depth(tree_path: Path) -> None:
+    assert [path for path, _, _ in walk_tree("root", max_depth=1)] == ["root"]
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_walk_tree_bytes(tree_path: Path) -> None:
+    result = list(walk_tree(b"root", sort=True))
+    assert result[0] == (b"root", [b"a", b"c"], [b"foo"])
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+            Symlink("loop", ".."),
+        ),
+        Symlink("link", "a"),
+    ),
+    chdir=True,
+)
+def test_walk_tree_symlinks(tree_path: Path) -> None:
+    result = sorted_walk(list(walk_tree("root")))
+    assert result == [
+        ("root", ["a", "link"], []),
+        ("root/a", ["loop"], ["foo"]),
+    ]
+
+    errors: "list[Exception]" = []
+    walk = walk_tree("root", followlinks=True, onerror=errors.append)
+    result = sorted_walk(list(walk))
+    assert result == [
+        ("root", ["a", "link"], []),
+        ("root/a", ["loop"], ["foo"]),
+        ("root/link", ["loop"], ["foo"]),
+    ]
+    assert len(errors) == 2
+    assert all(isinstance(error, LoopError) for error in errors)
+    assert {error.child for error in errors} == {"root/a/loop", "root/link/loop"}
+
+
+def test_walk_tree_parents() -> None:
+    with pytest.raises(ValueError):
+        next(walk_tree(".", parents=False))
+
+
+@pytest.mark.parametrize(
+    "option",
+    [
+        "follow_symlinks",
+        "min_depth",
+        "contents_first",
+        "threads",
+        "unique_inodes",
+        "path_type",
+        "count_skipped",
+        "count_bytes",
+        "order",
+    ],
+)
+def test_walk_tree_unsupported_options(option: str) -> None:
+    with pytest.raises(TypeError):
+        next(walk_tree(".", **{option: 1}))
+
+
+@pytest.mark.tree(TREE, chdir=True)
+def test_walk_tree_callback_depth(tree_path: Path) -> None:
+    filtered = []
+    keyed = []
+
+    def filter_entry(entry: DirEntry) -> bool:
+        filtered.append((entry.relative_path, entry.depth, entry.root))
+        return True
+
+    def sort_key(entry: DirEntry) -> str:
+        keyed.append((entry.relative_path, entry.depth, entry.root))
+        return entry.name
+
+    list(walk_tree("root", filter_entry=filter_entry, sort_key=sort_key))
+    assert sorted(filtered) == [
+        ("a", 1, "root"),
+        ("a/b", 2, "root"),
+        ("a/b/spam", 3, "root"),
+        ("a/bar", 2, "root"),
+        ("c", 1, "root"),
+        ("c/eggs", 2, "root"),
+        ("foo", 1, "root"),
+    ]
+    assert ("a/bar", 2, "root") in keyed
+    assert set(keyed) <= set(filtered)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            Directory(
+                "b",
+                Symlink("self", "."),
+            ),
+        ),
+    ),
+    chdir=True,
+)
+def test_walk_tree_error_depth(tree_path: Path) -> None:
+    errors: "list[Exception]" = []
+    list(walk_tree("root", followlinks=True, onerror=errors.append))
+    assert len(errors) == 1
+    assert isinstance(errors[0], LoopError)
+    assert errors[0].depth == 3
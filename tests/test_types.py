@@ -1,8 +1,9 @@
 import pickle
 from collections.abc import Iterator, Mapping, MutableMapping
 from copy import deepcopy
+from pathlib import Path
 
-from crabwalk import Types
+from crabwalk import Match, Types
 
 
 def test_mapping() -> None:
@@ -80,6 +81,35 @@ def test_add() -> None:
     assert types["py"] == ("*.py",)
 
 
+def test_add_def() -> None:
+    types = Types()
+    types.add_def("html:*.html")
+    types.add_def("css:*.css")
+    types.add_def(definition="css:*.scss")
+    types.add_def("web:include:html,css")
+    assert types["html"] == ("*.html",)
+    assert types["css"] == ("*.css", "*.scss")
+    assert types["web"] == ("*.html", "*.css", "*.scss")
+
+
+def test_from_file(tmp_path: Path) -> None:
+    path = tmp_path / "types"
+    path.write_text(
+        "# my types\n"
+        "\n"
+        "py: *.py, *.pyi\n"
+        "rust:*.rs\n"
+        "code:include:py,rust\n"
+    )
+    types = Types.from_file(path)
+    assert isinstance(types, Types)
+    assert dict(types) == {
+        "py": ("*.py", "*.pyi"),
+        "rust": ("*.rs",),
+        "code": ("*.py", "*.pyi", "*.rs"),
+    }
+
+
 def test_add_defaults() -> None:
     types = Types()
     types.add_defaults()
@@ -99,3 +129,40 @@ def test_pickle() -> None:
     t2 = pickle.loads(pickle.dumps(types))
     assert dict(types) == dict(t2)
     assert t2.__getstate__() == {"selections": [("select", "rust")]}
+
+
+def test_matched() -> None:
+    types = Types(py=["*.py", "*.pyi"], rust=["*.rs"], md=["*.md"])
+    assert types.matched("lib.py") is None
+
+    types.select("py")
+    types.negate("md")
+    assert types.matched("src/lib.pyi") == Match(
+        kind="types", whitelist=True, glob="*.pyi", file_type="py"
+    )
+    assert types.matched("README.md") == Match(
+        kind="types", whitelist=False, glob="*.md", file_type="md"
+    )
+    assert types.matched("src/lib.rs") == Match(kind="types", whitelist=False)
+    assert types.matched("src", is_dir=True) is None
+
+    types.select("rust")
+    assert types.matched("src/lib.rs") == Match(
+        kind="types", whitelist=True, glob="*.rs", file_type="rust"
+    )
+
+
+def test_type_of() -> None:
+    types = Types(py=["*.py", "*.pyi"], rust=["*.rs"])
+    types.select("rust")
+    assert types.type_of("lib.pyi") == Match(
+        kind="types", whitelist=True, glob="*.pyi", file_type="py"
+    )
+    assert types.type_of("lib.c") is None
+
+    types.add("c", "*.c")
+    assert types.type_of("lib.c") == Match(
+        kind="types", whitelist=True, glob="*.c", file_type="c"
+    )
+    del types["c"]
+    assert types.type_of("lib.c") is None
@@ -1,12 +1,15 @@
+import asyncio
 import os
 import sys
+import time
 from collections.abc import Callable, Iterator
+from datetime import datetime, timezone
 from pathlib import Path
 from unittest.mock import Mock
 
 import pytest
 
-from crabwalk import DirEntry, Overrides, Types, Walk
+from crabwalk import DirEntry, Match, Overrides, Types, Walk, WalkStats
 
 from .tree import Directory, File, Symlink
 
@@ -75,6 +78,260 @@ def test_max_depth(tree_path: Path, walk_paths: WalkPaths) -> None:
     assert list(walk_paths(walk)) == ["root", "root/1", "root/1/2"]
 
 
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "1",
+            Directory(
+                "2",
+                Directory("3"),
+            ),
+        ),
+    ),
+    chdir=True,
+)
+def test_min_depth(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk("root", min_depth=2, sort=True)
+    assert walk.min_depth == 2
+    assert list(walk_paths(walk)) == ["root/1/2", "root/1/2/3"]
+
+    with Walk("root", min_depth=1, max_depth=2, sort=True, path_type=str) as walk:
+        assert walk.next_batch(10) == ["root/1", "root/1/2"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "a",
+        File("foo"),
+        Directory(
+            "b",
+            File("bar"),
+            Directory("c"),
+        ),
+        Directory(
+            "d",
+            File("spam"),
+        ),
+    ),
+    Directory(
+        "e",
+        File("eggs"),
+    ),
+    chdir=True,
+)
+def test_contents_first(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk("a", "e", contents_first=True, sort=True)
+    assert walk.contents_first
+    assert list(walk_paths(walk)) == [
+        "a/b/bar",
+        "a/b/c",
+        "a/b",
+        "a/d/spam",
+        "a/d",
+        "a/foo",
+        "a",
+        "e/eggs",
+        "e",
+    ]
+
+
+def test_contents_first_threads() -> None:
+    with pytest.raises(ValueError):
+        with Walk(".", contents_first=True, threads=2) as walk:
+            next(walk)
+
+
+BFS_TREE = Directory(
+    "root",
+    File(".gitignore", "*.log\n"),
+    File("z"),
+    Directory(
+        "a",
+        File("debug.log"),
+        Directory(
+            "b",
+            File("spam.log"),
+            File("spam"),
+        ),
+    ),
+    Directory(
+        "c",
+        File(".gitignore", "eggs\n"),
+        File("eggs"),
+        Directory(
+            "d",
+            File("eggs"),
+            File("ham"),
+        ),
+    ),
+)
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk("root", order="bfs", sort=True, require_git=False)
+    assert walk.order == "bfs"
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/a",
+        "root/c",
+        "root/z",
+        "root/a/b",
+        "root/c/d",
+        "root/a/b/spam",
+        "root/c/d/ham",
+    ]
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+@pytest.mark.parametrize("hidden", [True, False])
+def test_order_bfs_same_entries(
+    tree_path: Path, walk_paths: WalkPaths, hidden: bool
+) -> None:
+    def walk(order: str) -> list[str]:
+        return sorted(walk_paths(Walk("root", order=order, hidden=hidden)))
+
+    assert walk("bfs") == walk("dfs")
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs_max_depth(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk("root", order="bfs", sort=True, max_depth=1, min_depth=1)
+    assert list(walk_paths(walk)) == [
+        "root/a",
+        "root/c",
+        "root/z",
+    ]
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs_filter_entry(tree_path: Path, walk_paths: WalkPaths) -> None:
+    filtered: list[str] = []
+
+    def filter_entry(entry: "DirEntry[str]") -> bool:
+        filtered.append(entry.path)
+        return entry.name != "c"
+
+    walk = Walk(
+        "root",
+        order="bfs",
+        sort=True,
+        require_git=False,
+        filter_entry=filter_entry,
+    )
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/a",
+        "root/z",
+        "root/a/b",
+        "root/a/b/spam",
+    ]
+    # Each entry is only filtered once, and ignored entries aren't filtered
+    assert filtered == [
+        "root/a",
+        "root/c",
+        "root/z",
+        "root/a/b",
+        "root/a/b/spam",
+    ]
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs_sort_key(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk(
+        "root",
+        order="bfs",
+        sort_key=lambda entry: entry.name,
+        reverse=True,
+        require_git=False,
+    )
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/z",
+        "root/c",
+        "root/a",
+        "root/c/d",
+        "root/a/b",
+        "root/c/d/ham",
+        "root/a/b/spam",
+    ]
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs_on_skip(tree_path: Path, walk_paths: WalkPaths) -> None:
+    def skipped(order: str) -> list[tuple[str, str]]:
+        skipped = []
+        walk = Walk(
+            "root",
+            order=order,
+            require_git=False,
+            on_skip=lambda path, match: skipped.append((path, match.kind)),
+        )
+        list(walk_paths(walk))
+        return sorted(skipped)
+
+    assert skipped("bfs") == skipped("dfs") == [
+        (os.path.join("root", ".gitignore"), "hidden"),
+        (os.path.join("root", "a", "b", "spam.log"), "gitignore"),
+        (os.path.join("root", "a", "debug.log"), "gitignore"),
+        (os.path.join("root", "c", ".gitignore"), "hidden"),
+        (os.path.join("root", "c", "d", "eggs"), "gitignore"),
+        (os.path.join("root", "c", "eggs"), "gitignore"),
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("a"),
+        Directory(
+            "b",
+            Symlink("loop", ".."),
+            File("c"),
+        ),
+    ),
+    chdir=True,
+)
+def test_order_bfs_loop(tree_path: Path) -> None:
+    events = []
+    walk = Walk(
+        "root",
+        order="bfs",
+        follow_symlinks=True,
+        sort=True,
+        onerror=lambda exc: events.append(type(exc).__name__),
+    )
+    with walk:
+        events.extend(entry.path for entry in walk)
+    assert events == [
+        "root",
+        "root/a",
+        "root/b",
+        "root/b/c",
+        "LoopError",
+    ]
+
+
+@pytest.mark.tree(BFS_TREE, chdir=True)
+def test_order_bfs_skip_current_dir(tree_path: Path) -> None:
+    with Walk("root", order="bfs") as walk:
+        next(walk)
+        with pytest.raises(RuntimeError):
+            walk.skip_current_dir()
+
+
+def test_order_invalid() -> None:
+    with pytest.raises(ValueError):
+        Walk(".", order="random")  # type: ignore[call-overload]
+    with pytest.raises(ValueError):
+        with Walk(".", order="bfs", threads=2) as walk:
+            next(walk)
+    with pytest.raises(ValueError):
+        with Walk(".", order="bfs", contents_first=True) as walk:
+            next(walk)
+
+
 @pytest.mark.tree(
     Directory(
         "root",
@@ -105,6 +362,78 @@ def test_max_filesize(tree_path: Path, walk_paths: WalkPaths) -> None:
     assert list(walk_paths(walk)) == ["root", "root/one", "root/two"]
 
 
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("one", "x"),
+        Directory(
+            "sub",
+            File("two", "xx"),
+            File("three", "xxx"),
+        ),
+    ),
+    chdir=True,
+)
+def test_min_filesize(tree_path: Path, walk_paths: WalkPaths) -> None:
+    walk = Walk("root", min_filesize=2, sort=True)
+    assert walk.min_filesize == 2
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/sub",
+        "root/sub/three",
+        "root/sub/two",
+    ]
+
+    walk = Walk(
+        "root",
+        min_filesize=2,
+        max_filesize=2,
+        filter_entry=lambda entry: entry.name != "two",
+        sort=True,
+    )
+    assert list(walk_paths(walk)) == ["root", "root/sub"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("old.txt"),
+        File("new.txt"),
+        File("new.rs"),
+        Directory("dir"),
+    ),
+    chdir=True,
+)
+def test_time_filters(tree_path: Path, walk_paths: WalkPaths) -> None:
+    os.utime("root/old.txt", (1000, 1000))
+    os.utime("root/new.txt", (3000, 3000))
+    os.utime("root/new.rs", (3000, 3000))
+    os.utime("root/dir", (1000, 1000))
+
+    walk = Walk("root", modified_after=2000, sort=True)
+    assert walk.modified_after == 2000
+    assert list(walk_paths(walk)) == ["root", "root/dir", "root/new.rs", "root/new.txt"]
+
+    after = datetime.fromtimestamp(2000, timezone.utc)
+    walk = Walk("root", modified_after=after, types=Types(txt=["*.txt"]), sort=True)
+    walk.types.select("txt")
+    assert list(walk_paths(walk)) == ["root", "root/dir", "root/new.txt"]
+
+    walk = Walk("root", sort=True)
+    walk.modified_before = 2000
+    assert list(walk_paths(walk)) == ["root", "root/dir", "root/old.txt"]
+
+    walk = Walk("root", accessed_before=2000.5, sort=True)
+    assert walk.accessed_before == 2000.5
+    assert list(walk_paths(walk)) == ["root", "root/dir", "root/old.txt"]
+    assert walk.explain("root/new.rs") == Match(
+        kind="accessed_before", whitelist=False
+    )
+
+    with pytest.raises(TypeError):
+        Walk("root", modified_after="yesterday")  # type: ignore[call-overload]
+
+
 @pytest.mark.tree(
     Directory(
         "root",
@@ -392,6 +721,172 @@ def test_sort_exception(tree_path: Path) -> None:
                 pass
 
 
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("file10"),
+        File("File2", "12"),
+        File("file1", "123"),
+        Directory("dir"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize(
+    ("sort", "expected"),
+    [
+        ("name", ["File2", "dir", "file1", "file10"]),
+        ("name_ci", ["dir", "file1", "file10", "File2"]),
+        ("natural", ["File2", "dir", "file1", "file10"]),
+        ("dirs_first", ["dir", "File2", "file1", "file10"]),
+    ],
+)
+def test_sort_mode(
+    tree_path: Path, walk_paths: WalkPaths, sort: str, expected: "list[str]"
+) -> None:
+    walk = Walk("root", sort=sort, min_depth=1)
+    assert walk.sort == sort
+    assert [os.path.basename(path) for path in walk_paths(walk)] == expected
+
+    walk = Walk("root", sort=sort, reverse=True, min_depth=1)
+    assert walk.reverse
+    assert [os.path.basename(path) for path in walk_paths(walk)] == expected[::-1]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("b", "123"),
+        File("c", "1"),
+        File("a", "12"),
+        File("img2"),
+        File("img10"),
+    ),
+    chdir=True,
+)
+def test_sort_mode_metadata(tree_path: Path, walk_paths: WalkPaths) -> None:
+    for i, name in enumerate(["c", "a", "b", "img10", "img2"]):
+        os.utime(os.path.join("root", name), (1000 + i, 1000 + i))
+
+    def names(sort: str) -> "list[str]":
+        with Walk("root", sort=sort, min_depth=1, path_type=os.path.basename) as walk:
+            return list(walk)
+
+    assert names("mtime") == ["c", "a", "b", "img10", "img2"]
+    assert names("size") == ["img10", "img2", "c", "a", "b"]
+    assert names("natural") == ["a", "b", "c", "img2", "img10"]
+
+
+def test_sort_mode_invalid() -> None:
+    with pytest.raises(ValueError):
+        Walk(".", sort="random")  # type: ignore[call-overload]
+    walk = Walk(".")
+    with pytest.raises(ValueError):
+        walk.sort = "random"  # type: ignore[assignment]
+    with pytest.raises(ValueError, match="reverse requires sort or sort_key"):
+        Walk(".", reverse=True)
+    walk = Walk(".")
+    walk.reverse = True
+    with pytest.raises(ValueError, match="reverse requires sort or sort_key"):
+        with walk:
+            next(walk)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("a", "123"),
+        File("b", "1"),
+        Directory(
+            "c",
+            File("d", "12"),
+            File("e"),
+        ),
+    ),
+    chdir=True,
+)
+def test_sort_key_entry(tree_path: Path, walk_paths: WalkPaths) -> None:
+    def dirs_first(entry: DirEntry) -> "tuple[bool, str]":
+        return not entry.is_dir(), entry.name
+
+    walk = Walk("root", sort_key=dirs_first)
+    assert walk.sort_key is dirs_first
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/c",
+        "root/c/d",
+        "root/c/e",
+        "root/a",
+        "root/b",
+    ]
+
+    mock_key = Mock(wraps=lambda entry: (entry.is_dir(), entry.stat().st_size))
+    walk = Walk("root", sort_key=mock_key)
+    assert list(walk_paths(walk)) == [
+        "root",
+        "root/b",
+        "root/a",
+        "root/c",
+        "root/c/e",
+        "root/c/d",
+    ]
+    entries = [args[0] for _, args, _ in mock_key.mock_calls]
+    assert sorted((entry.path, entry.depth) for entry in entries) == [
+        ("root/a", 1),
+        ("root/b", 1),
+        ("root/c", 1),
+        ("root/c/d", 2),
+        ("root/c/e", 2),
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("b"),
+        Symlink("a", "missing"),
+        File("c"),
+    ),
+    chdir=True,
+)
+def test_sort_key_metadata_error(tree_path: Path, walk_paths: WalkPaths) -> None:
+    errors: list[Exception] = []
+    walk = Walk(
+        "root",
+        follow_symlinks=True,
+        sort_key=lambda entry: entry.name,
+        onerror=errors.append,
+    )
+    # The dangling symlink sorts first and is reported by the walker
+    assert list(walk_paths(walk)) == ["root", "root/b", "root/c"]
+    assert len(errors) == 1
+    assert isinstance(errors[0], FileNotFoundError)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+        File("bar"),
+    ),
+    chdir=True,
+)
+def test_sort_key_exception(tree_path: Path) -> None:
+    class MyError(Exception):
+        pass
+
+    def key(entry: DirEntry) -> str:
+        raise MyError
+
+    with pytest.raises(MyError):
+        with Walk("root", sort_key=key) as walk:
+            for _ in walk:
+                pass
+
+    with pytest.raises(ValueError):
+        with Walk("root", sort=True, sort_key=key) as walk:
+            next(walk)
+
+
 @pytest.mark.tree(
     Directory(
         "root",
@@ -454,6 +949,99 @@ def test_direntry_fspath_absolute(tree_path: Path, walk_entries: WalkEntries) ->
     assert os.fspath(foo) == foo.path == str(root_path / "foo")
 
 
+@pytest.mark.tree(
+    Directory(
+        "a",
+        File("foo"),
+        Directory(
+            "b",
+            File("bar"),
+        ),
+    ),
+    Directory(
+        "c",
+        File("baz"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [0, 2], ids=lambda threads: f"threads={threads}")
+def test_direntry_root_and_relative_path(
+    tree_path: Path, walk_entries: WalkEntries, threads: int
+) -> None:
+    entries = walk_entries(Walk("a", "a/b", "c", threads=threads))
+    assert sorted(
+        (entry.root, entry.relative_path, entry.path) for entry in entries
+    ) == [
+        ("a", ".", "a"),
+        ("a", "b", "a/b"),
+        ("a", "b/bar", "a/b/bar"),
+        ("a", "foo", "a/foo"),
+        ("a/b", ".", "a/b"),
+        ("a/b", "bar", "a/b/bar"),
+        ("c", ".", "c"),
+        ("c", "baz", "c/baz"),
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "k",
+        Directory(
+            "a",
+            File("bar"),
+            File("foo"),
+        ),
+        File("z"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize(
+    ("root", "relative_paths"),
+    [
+        ("k/", [".", "a", "a/bar", "a/foo", "z"]),
+        ("./k/a/", [".", "bar", "foo"]),
+    ],
+)
+def test_direntry_root_trailing_slash(
+    tree_path: Path, walk_entries: WalkEntries, root: str, relative_paths: list[str]
+) -> None:
+    filtered = []
+    keyed = []
+
+    def filter_entry(entry: DirEntry) -> bool:
+        filtered.append(entry.root)
+        return True
+
+    def sort_key(entry: DirEntry) -> str:
+        keyed.append(entry.root)
+        return entry.name
+
+    entries = list(
+        walk_entries(Walk(root, filter_entry=filter_entry, sort_key=sort_key))
+    )
+    assert [entry.relative_path for entry in entries] == relative_paths
+    assert {entry.root for entry in entries} == {root}
+    assert set(filtered) == set(keyed) == {root}
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "dir",
+            File("foo"),
+        ),
+    ),
+)
+def test_direntry_root_and_relative_path_bytes(
+    tree_path: Path, walk_entries: WalkEntries
+) -> None:
+    root_path = os.fsencode(tree_path / "root")
+    *_, foo = walk_entries(Walk(root_path, sort=True))
+    assert foo.root == root_path
+    assert foo.relative_path == os.path.join(b"dir", b"foo")
+
+
 @pytest.mark.tree(
     Directory(
         "root",
@@ -501,3 +1089,906 @@ def test_direntry_against_os_direntry(tree_path: Path, follow_symlinks: bool) ->
         assert os_entry.is_symlink() == cw_entry.is_symlink()
         assert os_entry.stat(follow_symlinks=follow_symlinks) == cw_entry.stat()
         assert cw_entry.follow_symlinks == follow_symlinks
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("file1"),
+        Directory("dir1"),
+        Symlink("link1", "file1"),
+        Symlink("link2", "dir1"),
+        Symlink("link3", "missing"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize(
+    "follow_symlinks",
+    [True, False],
+    ids=lambda follow_symlinks: f"follow_symlinks={follow_symlinks}",
+)
+def test_direntry_follow_symlinks_argument(
+    tree_path: Path, walk_entries: WalkEntries, follow_symlinks: bool
+) -> None:
+    with os.scandir("root") as it:
+        os_entries = sorted(it, key=lambda entry: entry.path)
+    cw_entries = sorted(
+        walk_entries(Walk("root", max_depth=1, min_depth=1)),
+        key=lambda entry: entry.path,
+    )
+
+    assert [entry.path for entry in cw_entries] == [
+        entry.path for entry in os_entries
+    ]
+    for os_entry, cw_entry in zip(os_entries, cw_entries):
+        assert os_entry.is_dir(follow_symlinks=follow_symlinks) == cw_entry.is_dir(
+            follow_symlinks=follow_symlinks
+        )
+        assert os_entry.is_file(follow_symlinks=follow_symlinks) == cw_entry.is_file(
+            follow_symlinks=follow_symlinks
+        )
+        assert not cw_entry.is_junction()
+        if cw_entry.name != "link3" or not follow_symlinks:
+            assert os_entry.stat(follow_symlinks=follow_symlinks) == cw_entry.stat(
+                follow_symlinks=follow_symlinks
+            )
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("file1", "hello"),
+        Directory("dir1"),
+        Symlink("link1", "file1"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize(
+    "prefetch_metadata",
+    [True, False],
+    ids=lambda prefetch_metadata: f"prefetch_metadata={prefetch_metadata}",
+)
+@pytest.mark.parametrize("threads", [0, 2], ids=lambda threads: f"threads={threads}")
+@pytest.mark.parametrize(
+    "follow_symlinks",
+    [True, False],
+    ids=lambda follow_symlinks: f"follow_symlinks={follow_symlinks}",
+)
+def test_direntry_metadata(
+    tree_path: Path, follow_symlinks: bool, threads: int, prefetch_metadata: bool
+) -> None:
+    with Walk(
+        "root",
+        follow_symlinks=follow_symlinks,
+        threads=threads,
+        prefetch_metadata=prefetch_metadata,
+    ) as walk:
+        assert walk.prefetch_metadata == prefetch_metadata
+        entries = list(walk)
+
+    assert len(entries) == 4
+    for entry in entries:
+        metadata = entry.metadata()
+        stat = os.stat(entry, follow_symlinks=follow_symlinks)
+        assert metadata.size == stat.st_size
+        assert metadata.mtime_ns == stat.st_mtime_ns
+        if sys.platform == "win32":
+            continue
+        assert metadata.mode == stat.st_mode
+        assert metadata.uid == stat.st_uid
+        assert metadata.gid == stat.st_gid
+        assert metadata.nlink == stat.st_nlink
+        assert metadata.dev == stat.st_dev
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+            File("bar"),
+        ),
+        Directory(
+            "b",
+            File(".hidden"),
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [0, 1, 4])
+def test_threads(tree_path: Path, walk_paths: WalkPaths, threads: int) -> None:
+    walk = Walk("root", threads=threads)
+    assert walk.threads == threads
+    assert sorted(walk_paths(walk)) == [
+        "root",
+        "root/a",
+        "root/a/bar",
+        "root/a/foo",
+        "root/b",
+        "root/b/spam",
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_threads_sort(tree_path: Path) -> None:
+    with pytest.raises(ValueError):
+        with Walk("root", threads=2, sort=True) as walk:
+            next(walk)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_threads_filter_entry_exception(tree_path: Path) -> None:
+    class MyError(Exception):
+        pass
+
+    def filter_entry(entry: DirEntry) -> bool:
+        raise MyError
+
+    with pytest.raises(MyError):
+        with Walk("root", filter_entry=filter_entry, threads=2) as walk:
+            for _ in walk:
+                pass
+
+
+@pytest.mark.tree(
+    Directory("root", *(File(str(i)) for i in range(2000))),
+    chdir=True,
+)
+def test_threads_close_early(tree_path: Path) -> None:
+    with Walk("root", threads=2) as walk:
+        next(walk)
+    with pytest.raises(RuntimeError):
+        next(walk)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            Directory(
+                "b",
+                File("foo"),
+            ),
+            File("bar"),
+        ),
+        Directory(
+            "c",
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+def test_skip_current_dir(tree_path: Path) -> None:
+    paths = []
+    with Walk("root", sort=True) as walk:
+        for entry in walk:
+            paths.append(entry.path)
+            if entry.name == "a":
+                walk.skip_current_dir()
+    assert paths == ["root", "root/a", "root/c", "root/c/spam"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("bar"),
+            File("baz"),
+            File("foo"),
+        ),
+        Directory(
+            "c",
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+def test_skip_current_dir_file(tree_path: Path) -> None:
+    paths = []
+    with Walk("root", sort=True) as walk:
+        for entry in walk:
+            paths.append(entry.path)
+            if entry.name == "bar":
+                walk.skip_current_dir()
+    assert paths == ["root", "root/a", "root/a/bar", "root/c", "root/c/spam"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+        ),
+        File("bar"),
+    ),
+    chdir=True,
+)
+def test_skip_current_dir_batch(tree_path: Path) -> None:
+    with Walk("root", sort=True) as walk:
+        assert [entry.path for entry in walk.next_batch(2)] == ["root", "root/a"]
+        walk.skip_current_dir()
+        assert [entry.path for entry in walk.next_batch(2)] == ["root/bar"]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_skip_current_dir_threads(tree_path: Path) -> None:
+    with Walk("root", threads=2) as walk:
+        next(walk)
+        with pytest.raises(RuntimeError):
+            walk.skip_current_dir()
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+        ),
+        File("bar"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("path_type", [str, bytes])
+def test_path_type(tree_path: Path, path_type: type) -> None:
+    with Walk("root", sort=True, path_type=path_type) as walk:
+        assert walk.path_type is path_type
+        paths = list(walk)
+    expected = ["root", "root/a", "root/a/foo", "root/bar"]
+    if path_type is bytes:
+        expected = [os.fsencode(path) for path in expected]
+    assert paths == expected
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_path_type_factory(tree_path: Path) -> None:
+    with Walk("root", sort=True, path_type=Path) as walk:
+        paths = list(walk)
+    assert paths == [Path("root"), Path("root/foo")]
+    assert all(isinstance(path, Path) for path in paths)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+        ),
+    ),
+    chdir=True,
+)
+def test_bytes_paths(tree_path: Path, walk_entries: WalkEntries) -> None:
+    entries = list(walk_entries(Walk(b"root", sort=True)))
+    assert [entry.path for entry in entries] == [b"root", b"root/a", b"root/a/foo"]
+    assert [entry.name for entry in entries] == [b"root", b"a", b"foo"]
+    assert [os.fspath(entry) for entry in entries] == [
+        b"root",
+        b"root/a",
+        b"root/a/foo",
+    ]
+    assert repr(entries[0]) == "<DirEntry b'root'>"
+
+    with Walk(b"root", sort=True, path_type=str) as walk:
+        assert list(walk) == ["root", "root/a", "root/a/foo"]
+
+    with Walk(b"root", sort=True, path_type=lambda path: ("x", path)) as walk:
+        assert next(walk) == ("x", b"root")
+
+
+@pytest.mark.skipif(sys.platform != "linux", reason="needs arbitrary bytes filenames")
+def test_bytes_paths_non_utf8(tmp_path: Path) -> None:
+    root = os.fsencode(tmp_path)
+    name = b"caf\xe9"
+    with open(os.path.join(root, name), "w"):
+        pass
+    with Walk(root, sort=True) as walk:
+        paths = [entry.path for entry in walk]
+    assert paths == [root, os.path.join(root, name)]
+
+
+def test_bytes_paths_mixed(tmp_path: Path) -> None:
+    with pytest.raises(TypeError):
+        with Walk(tmp_path, os.fsencode(tmp_path)) as walk:
+            next(walk)
+
+
+def test_path_type_invalid() -> None:
+    with pytest.raises(TypeError):
+        Walk(".", path_type=1)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+            File("bar"),
+        ),
+        Directory(
+            "b",
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+def test_next_batch(tree_path: Path) -> None:
+    with Walk("root", sort=True) as walk:
+        assert [entry.path for entry in walk.next_batch(4)] == [
+            "root",
+            "root/a",
+            "root/a/bar",
+            "root/a/foo",
+        ]
+        assert [entry.path for entry in walk.next_batch(4)] == [
+            "root/b",
+            "root/b/spam",
+        ]
+        assert walk.next_batch(4) == []
+        with pytest.raises(ValueError):
+            walk.next_batch(0)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+            File("bar"),
+        ),
+        Directory(
+            "b",
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+def test_batches(tree_path: Path) -> None:
+    with Walk("root", sort=True) as walk:
+        batches = [[entry.path for entry in batch] for batch in walk.batches(3)]
+    assert batches == [
+        ["root", "root/a", "root/a/bar"],
+        ["root/a/foo", "root/b", "root/b/spam"],
+    ]
+
+
+@pytest.mark.tree(
+    Directory("root", File("foo")),
+    chdir=True,
+)
+def test_batches_reentrant(tree_path: Path) -> None:
+    def filter_entry(entry: DirEntry) -> bool:
+        with pytest.raises(RuntimeError):
+            next(batches)
+        return True
+
+    walk = Walk("root", filter_entry=filter_entry)
+    batches = walk.batches(1)
+    with walk:
+        assert [[entry.path for entry in batch] for batch in batches] == [
+            ["root"],
+            ["root/foo"],
+        ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("a"),
+        Directory(
+            "b",
+            Symlink("loop", ".."),
+            File("c"),
+        ),
+        File("d"),
+    ),
+    chdir=True,
+)
+def test_next_batch_onerror_order(tree_path: Path) -> None:
+    def walk_events(batched: bool) -> list[str]:
+        events = []
+        walk = Walk(
+            "root",
+            follow_symlinks=True,
+            sort=True,
+            onerror=lambda exc: events.append(type(exc).__name__),
+        )
+        with walk:
+            if batched:
+                for batch in walk.batches(10):
+                    events.extend(entry.path for entry in batch)
+            else:
+                events.extend(entry.path for entry in walk)
+        return events
+
+    assert walk_events(batched=True) == walk_events(batched=False) == [
+        "root",
+        "root/a",
+        "root/b",
+        "root/b/c",
+        "LoopError",
+        "root/d",
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+        File("bar"),
+    ),
+    chdir=True,
+)
+def test_next_batch_filter_entry_exception(tree_path: Path) -> None:
+    class MyError(Exception):
+        pass
+
+    def filter_entry(entry: DirEntry) -> bool:
+        if entry.path == "root/foo":
+            raise MyError
+        return True
+
+    with Walk("root", filter_entry=filter_entry, sort=True) as walk:
+        assert [entry.path for entry in walk.next_batch(10)] == ["root", "root/bar"]
+        with pytest.raises(MyError):
+            walk.next_batch(10)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo"),
+            File("bar"),
+        ),
+        Directory(
+            "b",
+            File("spam"),
+        ),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [None, 2])
+def test_async(tree_path: Path, threads: "int | None") -> None:
+    async def main() -> list[str]:
+        async with Walk("root", threads=threads) as walk:
+            return [entry.path async for entry in walk]
+
+    assert sorted(asyncio.run(main())) == [
+        "root",
+        "root/a",
+        "root/a/bar",
+        "root/a/foo",
+        "root/b",
+        "root/b/spam",
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_async_filter_entry_exception(tree_path: Path) -> None:
+    class MyError(Exception):
+        pass
+
+    def filter_entry(entry: DirEntry) -> bool:
+        raise MyError
+
+    async def main() -> None:
+        async with Walk("root", filter_entry=filter_entry) as walk:
+            async for _ in walk:
+                pass
+
+    with pytest.raises(MyError):
+        asyncio.run(main())
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+    ),
+    chdir=True,
+)
+def test_async_after_sync(tree_path: Path) -> None:
+    async def main(walk: Walk) -> None:
+        await walk.__anext__()
+
+    with Walk("root") as walk:
+        next(walk)
+        with pytest.raises(RuntimeError):
+            asyncio.run(main(walk))
+
+
+@pytest.mark.tree(
+    Directory("root", *(File(str(i)) for i in range(2000))),
+    chdir=True,
+)
+def test_async_cancel(tree_path: Path) -> None:
+    walk = Walk("root")
+
+    async def consume() -> None:
+        async with walk:
+            async for _ in walk:
+                await asyncio.sleep(0)
+
+    async def main() -> None:
+        task = asyncio.create_task(consume())
+        await asyncio.sleep(0.01)
+        task.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await task
+
+    asyncio.run(main())
+    with pytest.raises(RuntimeError, match="Walk is closed"):
+        next(walk)
+
+
+@pytest.mark.tree(
+    Directory("root", *(File(str(i)) for i in range(10))),
+    chdir=True,
+)
+def test_async_cancel_waiting(tree_path: Path) -> None:
+    def filter_entry(entry: DirEntry) -> bool:
+        time.sleep(0.01)
+        return True
+
+    walk = Walk("root", filter_entry=filter_entry)
+
+    async def main() -> None:
+        task = asyncio.ensure_future(walk.__anext__())
+        await asyncio.sleep(0)
+        task.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await task
+
+    asyncio.run(main())
+    with pytest.raises(RuntimeError, match="Walk is closed"):
+        next(walk)
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File(".gitignore", "# comment\n*.log\n!keep.log\n"),
+        Directory(
+            "build",
+            File("out"),
+        ),
+        File(".ignore", "build/\n"),
+        File(".hidden"),
+        File("keep.log"),
+        File("debug.log"),
+        File("main.py"),
+    ),
+    chdir=True,
+)
+def test_explain(tree_path: Path) -> None:
+    walk = Walk("root", require_git=False)
+    assert walk.explain("root") is None
+    assert walk.explain("root/main.py") is None
+
+    match = walk.explain("root/debug.log")
+    assert match is not None
+    assert match.kind == "gitignore"
+    assert match.ignore
+    assert match.glob == "*.log"
+    assert match.path == os.path.join("root", ".gitignore")
+    assert match.line == 2
+
+    match = walk.explain("root/keep.log")
+    assert match == Match(
+        kind="gitignore",
+        whitelist=True,
+        glob="!keep.log",
+        path=os.path.join("root", ".gitignore"),
+        line=3,
+    )
+
+    match = walk.explain("root/build/out")
+    assert match is not None
+    assert match.kind == "ignore"
+    assert match.glob == "build/"
+
+    assert walk.explain("root/.hidden") == Match(kind="hidden", whitelist=False)
+
+    with pytest.raises(ValueError):
+        walk.explain("elsewhere/main.py")
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo.py"),
+        File("foo.rs", "xxx"),
+        File("foo.txt"),
+    ),
+    chdir=True,
+)
+def test_explain_filters(tree_path: Path) -> None:
+    types = Types(py=["*.py"], rust=["*.rs"])
+    types.select("rust")
+    walk = Walk(
+        "root",
+        overrides=Overrides(["!*.txt"], path="root"),
+        types=types,
+        max_filesize=2,
+    )
+    assert walk.explain("root/foo.txt") == Match(
+        kind="override", whitelist=False, glob="!*.txt"
+    )
+    assert walk.explain("root/foo.py") == Match(kind="types", whitelist=False)
+    assert walk.explain("root/foo.rs") == Match(kind="max_filesize", whitelist=False)
+
+    walk.max_filesize = None
+    assert walk.explain("root/foo.rs") == Match(
+        kind="types", whitelist=True, glob="*.rs", file_type="rust"
+    )
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File(".gitignore", "*.log\n"),
+        Directory(
+            "src",
+            File("main.py"),
+            File("main.rs", "xxx"),
+            File("debug.log"),
+        ),
+        File(".hidden"),
+        File("notes.txt"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [0, 2], ids=lambda threads: f"threads={threads}")
+def test_on_skip(tree_path: Path, walk_paths: WalkPaths, threads: int) -> None:
+    skipped: list[tuple[str, Match]] = []
+    walk = Walk(
+        "root",
+        require_git=False,
+        overrides=Overrides(["!*.txt"], path="root"),
+        max_filesize=2,
+        on_skip=lambda path, match: skipped.append((path, match)),
+        threads=threads,
+    )
+    assert sorted(walk_paths(walk)) == ["root", "root/src", "root/src/main.py"]
+    assert sorted(skipped) == [
+        (os.path.join("root", ".gitignore"), Match(kind="hidden", whitelist=False)),
+        (os.path.join("root", ".hidden"), Match(kind="hidden", whitelist=False)),
+        (
+            os.path.join("root", "notes.txt"),
+            Match(kind="override", whitelist=False, glob="!*.txt"),
+        ),
+        (
+            os.path.join("root", "src", "debug.log"),
+            Match(
+                kind="gitignore",
+                whitelist=False,
+                glob="*.log",
+                path=os.path.join("root", ".gitignore"),
+                line=1,
+            ),
+        ),
+        (
+            os.path.join("root", "src", "main.rs"),
+            Match(kind="max_filesize", whitelist=False),
+        ),
+    ]
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File(".hidden"),
+            Directory(
+                "b",
+                File(".hidden"),
+            ),
+        ),
+    ),
+    chdir=True,
+)
+def test_on_skip_depth(tree_path: Path) -> None:
+    on_skip = Mock()
+    with Walk("root", min_depth=2, max_depth=2, on_skip=on_skip) as walk:
+        assert walk.on_skip is on_skip
+        assert [entry.path for entry in walk.next_batch(10)] == ["root/a/b"]
+    # Reported for a directory which isn't yielded, but not for a directory
+    # which isn't descended into
+    on_skip.assert_called_once_with(
+        os.path.join("root", "a", ".hidden"), Match(kind="hidden", whitelist=False)
+    )
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File(".hidden"),
+    ),
+    chdir=True,
+)
+def test_on_skip_exception(tree_path: Path) -> None:
+    class MyError(Exception):
+        pass
+
+    def on_skip(path: str, match: Match) -> None:
+        raise MyError
+
+    with pytest.raises(MyError):
+        with Walk("root", on_skip=on_skip) as walk:
+            for _ in walk:
+                pass
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo", "abc"),
+        File(".hidden"),
+        Directory(
+            "dir",
+            File("bar", "de"),
+        ),
+        Symlink("link", "missing"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [0, 2], ids=lambda threads: f"threads={threads}")
+def test_stats(tree_path: Path, threads: int) -> None:
+    walk = Walk(
+        "root",
+        follow_symlinks=True,
+        count_skipped=True,
+        count_bytes=True,
+        threads=threads,
+    )
+    assert walk.count_skipped and walk.count_bytes
+    assert walk.stats == WalkStats(0, 0, 0, 0, 0, 0, 0, 0.0)
+    with walk:
+        assert len(list(walk)) == 4
+    stats = walk.stats
+    assert stats[:7] == (2, 2, 0, 1, 0, 1, 5)
+    assert stats.elapsed > 0
+    # The clock stopped when the walk finished
+    assert walk.stats.elapsed == stats.elapsed
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo"),
+        Symlink("link", "foo"),
+    ),
+    chdir=True,
+)
+def test_stats_without_options(tree_path: Path) -> None:
+    with Walk("root") as walk:
+        assert not walk.count_skipped and not walk.count_bytes
+        assert len(walk.next_batch(10)) == 3
+        assert walk.next_batch(10) == []
+    assert walk.stats == WalkStats(
+        dirs=1,
+        files=1,
+        symlinks=1,
+        errors=0,
+        skipped_ignored=None,
+        skipped_hidden=None,
+        bytes=None,
+        elapsed=walk.stats.elapsed,
+    )
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        File("foo", "abc"),
+        File("bar.log", "de"),
+    ),
+    chdir=True,
+)
+def test_stats_count_options(tree_path: Path) -> None:
+    # Counting doesn't depend on on_skip or prefetch_metadata
+    skipped = []
+    walk = Walk(
+        "root",
+        overrides=Overrides(["!*.log"], path="root"),
+        on_skip=lambda path, match: skipped.append(path),
+        prefetch_metadata=True,
+    )
+    with walk:
+        assert len(list(walk)) == 2
+    assert skipped == ["root/bar.log"]
+    assert walk.stats[4:7] == (None, None, None)
+
+    walk = Walk(
+        "root",
+        overrides=Overrides(["!*.log"], path="root"),
+        count_skipped=True,
+        count_bytes=True,
+    )
+    with walk:
+        (entry,) = [entry for entry in walk if entry.name == "foo"]
+    assert walk.stats[4:7] == (1, 0, 3)
+    assert entry.metadata().size == 3
+
+
+@pytest.mark.tree(
+    Directory(
+        "root",
+        Directory(
+            "a",
+            File("foo", "abc"),
+        ),
+        Directory(
+            "b",
+            File("bar"),
+        ),
+        Symlink("link", "a"),
+    ),
+    chdir=True,
+)
+@pytest.mark.parametrize("threads", [None, 2], ids=lambda threads: f"threads={threads}")
+def test_unique_inodes(
+    tree_path: Path, walk_paths: WalkPaths, threads: "int | None"
+) -> None:
+    os.link("root/a/foo", "root/b/hardlink")
+    walk = Walk("root", follow_symlinks=True, sort=threads is None, threads=threads)
+    assert len(list(walk_paths(walk))) == 8
+
+    walk = Walk(
+        "root",
+        follow_symlinks=True,
+        sort=threads is None,
+        threads=threads,
+        unique_inodes=True,
+    )
+    assert walk.unique_inodes
+    paths = list(walk_paths(walk))
+    unique_paths = ["root", "root/a", "root/a/foo", "root/b", "root/b/bar"]
+    if threads is None:
+        # The first path found is yielded
+        assert paths == unique_paths
+    else:
+        assert len(paths) == 5
+        assert {os.stat(path).st_ino for path in paths} == {
+            os.stat(path).st_ino for path in unique_paths
+        }